@@ -5,6 +5,13 @@ pub(crate) mod err;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use tor_error::internal;
 use tor_key_forge::{EncodableItem, ErasedKey, KeystoreItem, KeystoreItemType};
 
@@ -12,8 +19,35 @@ use crate::keystore::ephemeral::err::ArtiEphemeralKeystoreError;
 use crate::Error;
 use crate::{ArtiPath, KeyPath, KeySpecifier, Keystore, KeystoreId};
 
+/// A CBOR-serializable snapshot of a [`ArtiEphemeralKeystore`]'s key dictionary.
+#[derive(Serialize, Deserialize)]
+struct KeySnapshot {
+    /// The `(path, item_type, item)` triples making up the key dictionary.
+    entries: Vec<(ArtiPath, KeystoreItemType, KeystoreItem)>,
+}
+
+/// A hybrid-encrypted, multi-recipient envelope for a [`KeySnapshot`].
+///
+/// Serialized to CBOR as `{ nonce, ciphertext, wrapped_keys }`. The content
+/// key (AES-256-GCM) is generated fresh per export and wrapped once per
+/// recipient with RSA-OAEP, so any one recipient's private key unwraps it.
+#[derive(Serialize, Deserialize)]
+struct EncryptedEnvelope {
+    /// The 12-byte AES-GCM nonce used for `ciphertext`.
+    nonce: Vec<u8>,
+    /// The CBOR-encoded [`KeySnapshot`], encrypted under the content key.
+    ciphertext: Vec<u8>,
+    /// The content key, RSA-OAEP-wrapped once per recipient.
+    wrapped_keys: Vec<Vec<u8>>,
+}
+
 /// The identifier of a key stored in the `ArtiEphemeralKeystore`.
-type KeyIdent = (ArtiPath, KeystoreItemType);
+///
+/// Keyed on `ArtiPath` alone: the `KeystoreItemType` a lookup asks for is
+/// checked against the stored [`KeystoreItem`]'s own `item_type()` rather
+/// than folded into the map key, so a type mismatch is visible as corruption
+/// instead of silently looking like a missing key.
+type KeyIdent = ArtiPath;
 
 /// The Ephemeral Arti key store
 ///
@@ -41,6 +75,159 @@ impl ArtiEphemeralKeystore {
             key_dictionary: Default::default(),
         }
     }
+
+    /// Like [`Keystore::insert`], but refuses to overwrite an existing entry
+    /// at `key_spec`'s `ArtiPath`, returning
+    /// [`ArtiEphemeralKeystoreError::KeyAlreadyExists`] instead. Mirrors the
+    /// higher-level `KeyMgr` semantics for generating a fresh key, so callers
+    /// get a clear signal rather than silently clobbering what's there.
+    pub fn insert_new(
+        &self,
+        key: &dyn EncodableItem,
+        key_spec: &dyn KeySpecifier,
+        item_type: &KeystoreItemType,
+    ) -> Result<(), Error> {
+        let arti_path = key_spec
+            .arti_path()
+            .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
+        let key_data = key.as_keystore_item()?;
+
+        if key_data.item_type()? != *item_type {
+            return Err(internal!(
+                "the specified KeystoreItemType does not match key type of the inserted key?!"
+            )
+            .into());
+        }
+
+        let mut key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
+        if key_dictionary.contains_key(&arti_path) {
+            return Err(ArtiEphemeralKeystoreError::KeyAlreadyExists {
+                arti_path: arti_path.to_string(),
+            }
+            .into());
+        }
+
+        let _ = key_dictionary.insert(arti_path, key_data);
+        Ok(())
+    }
+
+    /// Snapshot this keystore's key dictionary, encrypt it with a freshly
+    /// generated AES-256-GCM content key, and wrap that content key once per
+    /// `recipients` entry with RSA-OAEP.
+    ///
+    /// The result is a self-contained CBOR blob (`{ nonce, ciphertext,
+    /// wrapped_keys }`) that any one of the recipients' private keys can
+    /// decrypt via [`import_encrypted`](Self::import_encrypted). Useful for
+    /// handing an in-memory identity off to another process or a cold
+    /// backup, without ever writing the keys to disk in plaintext.
+    pub fn export_encrypted(&self, recipients: &[RsaPublicKey]) -> Result<Vec<u8>, Error> {
+        let entries: Vec<(ArtiPath, KeystoreItemType, KeystoreItem)> = {
+            let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
+            key_dictionary
+                .iter()
+                .map(|(path, item)| Ok((path.clone(), item.item_type()?, item.clone())))
+                .collect::<Result<Vec<_>, Error>>()?
+        };
+
+        let mut plaintext = Vec::new();
+        ciborium::ser::into_writer(&KeySnapshot { entries }, &mut plaintext)
+            .map_err(|e| ArtiEphemeralKeystoreError::SnapshotEncodeError(e.to_string()))?;
+
+        let mut content_key_bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut content_key_bytes);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key_bytes));
+
+        let mut nonce_bytes = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+
+        let ciphertext = cipher
+            .encrypt(nonce, plaintext.as_slice())
+            .map_err(|_| ArtiEphemeralKeystoreError::EncryptionError)?;
+
+        let padding = Oaep::new::<Sha256>();
+        let wrapped_keys = recipients
+            .iter()
+            .map(|recipient| {
+                recipient
+                    .encrypt(&mut OsRng, padding.clone(), &content_key_bytes)
+                    .map_err(|_| ArtiEphemeralKeystoreError::EncryptionError.into())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let envelope = EncryptedEnvelope {
+            nonce: nonce_bytes.to_vec(),
+            ciphertext,
+            wrapped_keys,
+        };
+
+        let mut out = Vec::new();
+        ciborium::ser::into_writer(&envelope, &mut out)
+            .map_err(|e| ArtiEphemeralKeystoreError::SnapshotEncodeError(e.to_string()))?;
+
+        Ok(out)
+    }
+
+    /// Decrypt a blob produced by [`export_encrypted`](Self::export_encrypted)
+    /// with `key`, and re-insert every entry it contains into this keystore.
+    ///
+    /// Tries every wrapped content key in the envelope against `key` until
+    /// one unwraps; this is what makes the blob decryptable by any one of
+    /// the original recipients, not just the first. Every entry goes through
+    /// the same `item_type` match check as [`insert`](Keystore::insert), and
+    /// is checked for a conflicting existing entry, before any of them are
+    /// inserted -- a rejected import leaves this keystore unchanged rather
+    /// than partially applied.
+    pub fn import_encrypted(&self, blob: &[u8], key: &RsaPrivateKey) -> Result<(), Error> {
+        let envelope: EncryptedEnvelope = ciborium::de::from_reader(blob)
+            .map_err(|e| ArtiEphemeralKeystoreError::SnapshotDecodeError(e.to_string()))?;
+
+        let padding = Oaep::new::<Sha256>();
+        let content_key_bytes = envelope
+            .wrapped_keys
+            .iter()
+            .find_map(|wrapped| key.decrypt(padding.clone(), wrapped).ok())
+            .ok_or(ArtiEphemeralKeystoreError::NoMatchingRecipient)?;
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key_bytes));
+        let nonce = Nonce::from_slice(&envelope.nonce);
+        let plaintext = cipher
+            .decrypt(nonce, envelope.ciphertext.as_slice())
+            .map_err(|_| ArtiEphemeralKeystoreError::DecryptionError)?;
+
+        let snapshot: KeySnapshot = ciborium::de::from_reader(plaintext.as_slice())
+            .map_err(|e| ArtiEphemeralKeystoreError::SnapshotDecodeError(e.to_string()))?;
+
+        let mut key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
+
+        // Validate every entry before inserting any of them, so a snapshot
+        // that fails partway through (a type mismatch or collision on its
+        // Nth entry) leaves this keystore completely untouched rather than
+        // applying the first N-1 entries and rejecting the rest.
+        for (arti_path, item_type, item) in &snapshot.entries {
+            if item.item_type()? != *item_type {
+                return Err(internal!(
+                    "encrypted snapshot entry's item_type does not match its stored KeystoreItemType"
+                )
+                .into());
+            }
+            // Mirror `insert_new`'s refuse-to-overwrite semantics: an import
+            // landing on an existing entry is surprising enough that it
+            // should surface as an error, not silently clobber what's there.
+            if key_dictionary.contains_key(arti_path) {
+                return Err(ArtiEphemeralKeystoreError::KeyAlreadyExists {
+                    arti_path: arti_path.to_string(),
+                }
+                .into());
+            }
+        }
+
+        for (arti_path, _item_type, item) in snapshot.entries {
+            let _ = key_dictionary.insert(arti_path, item);
+        }
+
+        Ok(())
+    }
 }
 
 impl Keystore for ArtiEphemeralKeystore {
@@ -57,8 +244,10 @@ impl Keystore for ArtiEphemeralKeystore {
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
         let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
-        let contains_key = key_dictionary.contains_key(&(arti_path, item_type.clone()));
-        Ok(contains_key)
+        match key_dictionary.get(&arti_path) {
+            Some(item) => check_item_type(&arti_path, item, item_type).map(|()| true),
+            None => Ok(false),
+        }
     }
 
     fn get(
@@ -70,10 +259,10 @@ impl Keystore for ArtiEphemeralKeystore {
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
         let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
-        match key_dictionary.get(&(arti_path.clone(), item_type.clone())) {
-            Some(key) => {
-                let key: KeystoreItem = key.clone();
-                let key: ErasedKey = key.into_erased()?;
+        match key_dictionary.get(&arti_path) {
+            Some(item) => {
+                check_item_type(&arti_path, item, item_type)?;
+                let key: ErasedKey = item.clone().into_erased()?;
                 Ok(Some(key))
             }
             None => Ok(None),
@@ -91,12 +280,6 @@ impl Keystore for ArtiEphemeralKeystore {
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
         let key_data = key.as_keystore_item()?;
 
-        // TODO: add item_type validation to Keystore::get and Keystore::remove.
-        // The presence of a key with a mismatched item_type can be either due to keystore
-        // corruption, or API misuse. We will need a new error type and corresponding ErrorKind for
-        // that).
-        //
-        // TODO: add item_type validation to ArtiNativeKeystore
         if key_data.item_type()? != *item_type {
             // This can never happen unless:
             //   * Keystore::insert is called directly with an incorrect KeystoreItemType for `key`, or
@@ -111,7 +294,7 @@ impl Keystore for ArtiEphemeralKeystore {
 
         // save to dictionary
         let mut key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
-        let _ = key_dictionary.insert((arti_path, item_type.clone()), key_data);
+        let _ = key_dictionary.insert(arti_path, key_data);
         Ok(())
     }
 
@@ -124,20 +307,48 @@ impl Keystore for ArtiEphemeralKeystore {
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
         let mut key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
-        Ok(key_dictionary
-            .remove(&(arti_path, item_type.clone()))
-            .map(|_| ()))
+        match key_dictionary.get(&arti_path) {
+            Some(item) => {
+                check_item_type(&arti_path, item, item_type)?;
+                key_dictionary.remove(&arti_path);
+                Ok(Some(()))
+            }
+            None => Ok(None),
+        }
     }
 
     fn list(&self) -> Result<Vec<(KeyPath, KeystoreItemType)>, Error> {
         let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
-        Ok(key_dictionary
-            .keys()
-            .map(|(arti_path, item_type)| (arti_path.clone().into(), item_type.clone()))
-            .collect())
+        key_dictionary
+            .iter()
+            .map(|(arti_path, item)| Ok((arti_path.clone().into(), item.item_type()?)))
+            .collect()
     }
 }
 
+/// Verify that `item`, stored at `arti_path`, reports itself as `requested`.
+///
+/// Returns [`ArtiEphemeralKeystoreError::KeystoreCorruptionError`] on
+/// mismatch: `insert` already checks this invariant at write time, so
+/// reaching a mismatch here means the dictionary itself is corrupted rather
+/// than the requested key simply being absent.
+fn check_item_type(
+    arti_path: &ArtiPath,
+    item: &KeystoreItem,
+    requested: &KeystoreItemType,
+) -> Result<(), Error> {
+    let actual = item.item_type()?;
+    if actual != *requested {
+        return Err(ArtiEphemeralKeystoreError::KeystoreCorruptionError {
+            arti_path: arti_path.to_string(),
+            requested: requested.clone(),
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     // @@ begin test lint list maintained by maint/add_warning @@
@@ -300,4 +511,138 @@ mod tests {
             .is_ok());
         assert_eq!(key_store.list().unwrap().len(), 1);
     }
+
+    #[test]
+    fn export_import_round_trip() {
+        let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
+        assert!(key_store
+            .insert(key().as_ref(), key_spec().as_ref(), &key_type())
+            .is_ok());
+
+        let mut rng = testing_rng();
+        let priv_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let pub_key = RsaPublicKey::from(&priv_key);
+
+        let blob = key_store.export_encrypted(&[pub_key]).unwrap();
+
+        let imported = ArtiEphemeralKeystore::new("test-ephemeral-imported".to_string());
+        assert!(imported.import_encrypted(&blob, &priv_key).is_ok());
+        assert!(imported
+            .contains(key_spec().as_ref(), &key_type())
+            .unwrap());
+        assert_eq!(imported.list().unwrap(), key_store.list().unwrap());
+    }
+
+    #[test]
+    fn import_encrypted_rejects_wrong_key() {
+        let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
+        assert!(key_store
+            .insert(key().as_ref(), key_spec().as_ref(), &key_type())
+            .is_ok());
+
+        let mut rng = testing_rng();
+        let recipient_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let recipient_pub = RsaPublicKey::from(&recipient_key);
+        let blob = key_store.export_encrypted(&[recipient_pub]).unwrap();
+
+        let wrong_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let imported = ArtiEphemeralKeystore::new("test-ephemeral-imported".to_string());
+        assert!(imported.import_encrypted(&blob, &wrong_key).is_err());
+        assert!(imported.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn import_encrypted_rejects_tampered_ciphertext() {
+        let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
+        assert!(key_store
+            .insert(key().as_ref(), key_spec().as_ref(), &key_type())
+            .is_ok());
+
+        let mut rng = testing_rng();
+        let priv_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let pub_key = RsaPublicKey::from(&priv_key);
+        let blob = key_store.export_encrypted(&[pub_key]).unwrap();
+
+        // Flip the last byte of the envelope's AES-GCM ciphertext (its tag)
+        // and re-encode the envelope, so the wrapped key still unwraps but
+        // decryption fails its authentication check.
+        let mut envelope: EncryptedEnvelope = ciborium::de::from_reader(blob.as_slice()).unwrap();
+        let last = envelope.ciphertext.len() - 1;
+        envelope.ciphertext[last] ^= 0xff;
+        let mut blob = Vec::new();
+        ciborium::ser::into_writer(&envelope, &mut blob).unwrap();
+
+        let imported = ArtiEphemeralKeystore::new("test-ephemeral-imported".to_string());
+        assert!(imported.import_encrypted(&blob, &priv_key).is_err());
+        assert!(imported.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn import_encrypted_does_not_clobber_existing_entry() {
+        let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
+        assert!(key_store
+            .insert(key().as_ref(), key_spec().as_ref(), &key_type())
+            .is_ok());
+
+        let mut rng = testing_rng();
+        let priv_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let pub_key = RsaPublicKey::from(&priv_key);
+        let blob = key_store.export_encrypted(&[pub_key]).unwrap();
+
+        // The target keystore already has an entry at the same ArtiPath;
+        // importing must refuse to overwrite it rather than silently
+        // clobbering it.
+        let target = ArtiEphemeralKeystore::new("test-ephemeral-target".to_string());
+        assert!(target
+            .insert(key().as_ref(), key_spec().as_ref(), &key_type())
+            .is_ok());
+
+        assert!(target.import_encrypted(&blob, &priv_key).is_err());
+    }
+
+    #[test]
+    fn import_encrypted_is_atomic_on_partial_failure() {
+        let mut rng = testing_rng();
+        let priv_key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let pub_key = RsaPublicKey::from(&priv_key);
+
+        let good_item = key().as_keystore_item().unwrap();
+
+        // Two entries sharing an ArtiPath: the first, on its own, would
+        // import cleanly; the second's declared item_type doesn't match its
+        // actual item. The whole import must fail without the first entry
+        // having been inserted, even though it was validated first.
+        let entries = vec![
+            (key_spec().arti_path().unwrap(), key_type(), good_item.clone()),
+            (key_spec().arti_path().unwrap(), key_type_bad(), good_item),
+        ];
+        let mut plaintext = Vec::new();
+        ciborium::ser::into_writer(&KeySnapshot { entries }, &mut plaintext).unwrap();
+
+        let mut content_key_bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut content_key_bytes);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key_bytes));
+        let mut nonce_bytes = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+        let ciphertext = cipher.encrypt(nonce, plaintext.as_slice()).unwrap();
+
+        let padding = Oaep::new::<Sha256>();
+        let wrapped_keys = vec![pub_key.encrypt(&mut OsRng, padding, &content_key_bytes).unwrap()];
+        let envelope = EncryptedEnvelope {
+            nonce: nonce_bytes.to_vec(),
+            ciphertext,
+            wrapped_keys,
+        };
+        let mut blob = Vec::new();
+        ciborium::ser::into_writer(&envelope, &mut blob).unwrap();
+
+        let target = ArtiEphemeralKeystore::new("test-ephemeral-target".to_string());
+        assert!(target.import_encrypted(&blob, &priv_key).is_err());
+        // The first entry would have succeeded in isolation -- it must not
+        // have been inserted just because it was validated before the
+        // entry that ultimately failed.
+        assert!(!target.contains(key_spec().as_ref(), &key_type()).unwrap());
+        assert!(target.list().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,84 @@
+//! An error type for the ephemeral keystore.
+
+use tor_error::{ErrorKind, HasKind};
+use tor_key_forge::KeystoreItemType;
+
+use crate::{ArtiPathUnavailableError, Error};
+
+/// An error returned by the ephemeral key store.
+#[derive(thiserror::Error, Clone, Debug)]
+#[non_exhaustive]
+pub enum ArtiEphemeralKeystoreError {
+    /// The [`ArtiPath`](crate::ArtiPath) of the requested key is unavailable.
+    #[error("Arti path unavailable")]
+    ArtiPathUnavailableError(#[from] ArtiPathUnavailableError),
+
+    /// Failed to encode the key dictionary as CBOR for an encrypted snapshot.
+    #[error("failed to encode keystore snapshot: {0}")]
+    SnapshotEncodeError(String),
+
+    /// Failed to decode an encrypted snapshot back into a key dictionary.
+    #[error("failed to decode keystore snapshot: {0}")]
+    SnapshotDecodeError(String),
+
+    /// Encrypting the snapshot with AES-256-GCM, or wrapping its content key
+    /// for a recipient with RSA-OAEP, failed.
+    #[error("failed to encrypt keystore snapshot")]
+    EncryptionError,
+
+    /// AES-256-GCM decryption of the snapshot failed, most likely because the
+    /// blob was tampered with or the unwrapped content key was wrong.
+    #[error("failed to decrypt keystore snapshot")]
+    DecryptionError,
+
+    /// None of the envelope's wrapped content keys could be unwrapped with
+    /// the supplied RSA private key.
+    #[error("no wrapped key in the envelope matches the supplied private key")]
+    NoMatchingRecipient,
+
+    /// A key was found by [`ArtiPath`](crate::ArtiPath), but its own
+    /// `KeystoreItemType` doesn't match the type it was looked up under.
+    /// This should only be reachable via a bug elsewhere in this crate, since
+    /// `insert` already checks this invariant at write time.
+    #[error("key at {arti_path} has type {actual:?}, not the requested {requested:?}")]
+    KeystoreCorruptionError {
+        /// The path the mismatched key was found at.
+        arti_path: String,
+        /// The `KeystoreItemType` the caller asked for.
+        requested: KeystoreItemType,
+        /// The `KeystoreItemType` the stored item actually reports.
+        actual: KeystoreItemType,
+    },
+
+    /// [`ArtiEphemeralKeystore::insert_new`](super::ArtiEphemeralKeystore::insert_new)
+    /// or [`ArtiEphemeralKeystore::import_encrypted`](super::ArtiEphemeralKeystore::import_encrypted)
+    /// found an existing entry at the same `ArtiPath` (of any `KeystoreItemType`).
+    #[error("key already exists at {arti_path}")]
+    KeyAlreadyExists {
+        /// The path that already has an entry.
+        arti_path: String,
+    },
+}
+
+impl HasKind for ArtiEphemeralKeystoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ArtiEphemeralKeystoreError::ArtiPathUnavailableError(e) => e.kind(),
+            ArtiEphemeralKeystoreError::SnapshotEncodeError(_)
+            | ArtiEphemeralKeystoreError::SnapshotDecodeError(_)
+            | ArtiEphemeralKeystoreError::EncryptionError
+            | ArtiEphemeralKeystoreError::DecryptionError
+            | ArtiEphemeralKeystoreError::NoMatchingRecipient
+            | ArtiEphemeralKeystoreError::KeystoreCorruptionError { .. } => {
+                ErrorKind::KeystoreCorrupted
+            }
+            ArtiEphemeralKeystoreError::KeyAlreadyExists { .. } => ErrorKind::BadApiUsage,
+        }
+    }
+}
+
+impl From<ArtiEphemeralKeystoreError> for Error {
+    fn from(e: ArtiEphemeralKeystoreError) -> Self {
+        Error::Keystore(std::sync::Arc::new(e))
+    }
+}
@@ -0,0 +1,119 @@
+//! A structured, classified error type for the onion-service client.
+//!
+//! Before this, failures were funneled through `anyhow!("...")` strings, so
+//! callers couldn't tell a transient circuit failure (worth retrying with a
+//! fresh circuit) from a permanent one (bad onion address, TLS policy
+//! rejection, directory not bootstrapped). [`HsError`] carries that
+//! classification alongside the underlying cause.
+
+use std::fmt;
+
+/// Whether retrying the failed operation might succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retriable {
+    /// The same operation might succeed on a fresh attempt (e.g. with a new circuit).
+    Transient,
+    /// Retrying without changing something (address, config, TLS policy) won't help.
+    Permanent,
+}
+
+/// A classified failure from the onion-service client.
+#[derive(Debug)]
+pub enum HsError {
+    /// The Tor client hasn't finished bootstrapping / has no usable netdir yet.
+    NotBootstrapped(anyhow::Error),
+    /// Building or extending the circuit to the hidden service failed.
+    CircuitFailed(anyhow::Error),
+    /// A stream operation (connect, read, write) timed out.
+    StreamTimeout(anyhow::Error),
+    /// The TLS handshake, or the certificate policy, rejected the connection.
+    TlsRejected(anyhow::Error),
+    /// The supplied onion address or relay fingerprint was malformed.
+    InvalidOnionAddress(anyhow::Error),
+    /// An HTTP-level failure: malformed response, unsupported encoding, etc.
+    Http(anyhow::Error),
+}
+
+impl HsError {
+    pub fn not_bootstrapped(e: impl Into<anyhow::Error>) -> Self {
+        Self::NotBootstrapped(e.into())
+    }
+
+    pub fn circuit_failed(e: impl Into<anyhow::Error>) -> Self {
+        Self::CircuitFailed(e.into())
+    }
+
+    pub fn stream_timeout(e: impl Into<anyhow::Error>) -> Self {
+        Self::StreamTimeout(e.into())
+    }
+
+    pub fn tls_rejected(e: impl Into<anyhow::Error>) -> Self {
+        Self::TlsRejected(e.into())
+    }
+
+    pub fn invalid_onion_address(e: impl Into<anyhow::Error>) -> Self {
+        Self::InvalidOnionAddress(e.into())
+    }
+
+    pub fn http(e: impl Into<anyhow::Error>) -> Self {
+        Self::Http(e.into())
+    }
+
+    /// Whether retrying the operation (e.g. with a fresh circuit) might help.
+    pub fn retriable(&self) -> Retriable {
+        match self {
+            HsError::CircuitFailed(_) | HsError::StreamTimeout(_) => Retriable::Transient,
+            HsError::NotBootstrapped(_)
+            | HsError::TlsRejected(_)
+            | HsError::InvalidOnionAddress(_)
+            | HsError::Http(_) => Retriable::Permanent,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            HsError::NotBootstrapped(_) => "not bootstrapped",
+            HsError::CircuitFailed(_) => "circuit failed",
+            HsError::StreamTimeout(_) => "stream timeout",
+            HsError::TlsRejected(_) => "TLS rejected",
+            HsError::InvalidOnionAddress(_) => "invalid onion address",
+            HsError::Http(_) => "HTTP error",
+        }
+    }
+
+    fn cause(&self) -> &anyhow::Error {
+        match self {
+            HsError::NotBootstrapped(e)
+            | HsError::CircuitFailed(e)
+            | HsError::StreamTimeout(e)
+            | HsError::TlsRejected(e)
+            | HsError::InvalidOnionAddress(e)
+            | HsError::Http(e) => e,
+        }
+    }
+
+    /// Log this error once, at the level its retriability warrants, and
+    /// report the whole source chain rather than a single flattened string
+    /// (the same rationale as arti's `error_report!`).
+    pub fn report(&self) {
+        match self.retriable() {
+            Retriable::Transient => log::warn!("{} (transient): {:#}", self.kind(), self.cause()),
+            Retriable::Permanent => log::error!("{} (permanent): {:#}", self.kind(), self.cause()),
+        }
+    }
+}
+
+impl fmt::Display for HsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind(), self.cause())
+    }
+}
+
+impl std::error::Error for HsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.cause().as_ref())
+    }
+}
+
+/// Shorthand for a [`Result`] with an [`HsError`].
+pub type HsResult<T> = Result<T, HsError>;
@@ -0,0 +1,73 @@
+//! Hop specifications for an arbitrary-length custom onion-service circuit.
+//!
+//! [`HsHop`] generalizes the old "exactly guard/middle/exit, by RSA
+//! fingerprint" setup: a hop can still be pinned to a specific relay, or left
+//! as a set of constraints for [`TorHSConnector::resolve_custom_path`] to
+//! fill in via weighted selection.
+
+use tor_netdir::WeightRole;
+use tor_netdoc::doc::netstatus::RelayFlags;
+
+/// One hop of a custom circuit.
+#[derive(Clone)]
+pub enum HsHop {
+    /// A specific relay, identified by its RSA fingerprint (hex-encoded).
+    Relay(String),
+    /// Fill this hop with any relay satisfying the given constraints.
+    Constrained {
+        /// Flags the candidate relay must have (e.g. `GUARD`, `EXIT`, `HSDIR`).
+        required_flags: RelayFlags,
+        /// Consensus weight role used to pick among matching candidates.
+        role: WeightRole,
+        /// Require the candidate to have a second, IPv6 address.
+        ipv6_required: bool,
+        /// Reject candidates that share a declared family with an earlier hop.
+        exclude_same_family: bool,
+        /// Reject candidates that share a /16 with an earlier hop.
+        exclude_same_subnet: bool,
+    },
+}
+
+impl HsHop {
+    /// Pin this hop to a specific relay.
+    pub fn relay(fingerprint: impl Into<String>) -> Self {
+        Self::Relay(fingerprint.into())
+    }
+
+    /// Leave this hop to be filled by weighted selection among relays with
+    /// `required_flags`, for the given `role`. Family and /16 diversity
+    /// against earlier hops are excluded by default.
+    pub fn constrained(required_flags: RelayFlags, role: WeightRole) -> Self {
+        Self::Constrained {
+            required_flags,
+            role,
+            ipv6_required: false,
+            exclude_same_family: true,
+            exclude_same_subnet: true,
+        }
+    }
+
+    /// Require the filled-in relay to have an IPv6 address.
+    pub fn ipv6_required(mut self, required: bool) -> Self {
+        if let Self::Constrained { ipv6_required, .. } = &mut self {
+            *ipv6_required = required;
+        }
+        self
+    }
+
+    /// Toggle same-family exclusion against earlier hops.
+    pub fn exclude_same_family(mut self, exclude: bool) -> Self {
+        if let Self::Constrained { exclude_same_family, .. } = &mut self {
+            *exclude_same_family = exclude;
+        }
+        self
+    }
+
+    /// Toggle same-/16-subnet exclusion against earlier hops.
+    pub fn exclude_same_subnet(mut self, exclude: bool) -> Self {
+        if let Self::Constrained { exclude_same_subnet, .. } = &mut self {
+            *exclude_same_subnet = exclude;
+        }
+        self
+    }
+}
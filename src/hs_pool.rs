@@ -0,0 +1,92 @@
+//! Keep-alive connection pool for repeated requests to the same onion
+//! service, so fetching N pages from one hidden service doesn't rebuild the
+//! rendezvous circuit and stream N times.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arti_client::DataStream;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio_rustls::client::TlsStream;
+
+/// A pooled connection: either a plain stream, or a TLS session wrapping one.
+pub enum PooledConn {
+    Plain(DataStream),
+    Tls(Box<TlsStream<DataStream>>),
+}
+
+/// An idle connection and when it became idle, for timeout bookkeeping.
+struct IdleConn {
+    conn: PooledConn,
+    idle_since: Instant,
+}
+
+/// Retains idle connections keyed by `(onion_addr, port)` for reuse,
+/// evicting anything that has sat idle past `idle_timeout` and capping how
+/// many connections are kept per host.
+pub struct HsConnectionPool {
+    idle: Mutex<HashMap<(String, u16), VecDeque<IdleConn>>>,
+    idle_timeout: Duration,
+    max_per_host: usize,
+}
+
+impl HsConnectionPool {
+    /// Create a pool and spawn its background idle-timeout eviction task.
+    pub fn new(idle_timeout: Duration, max_per_host: usize) -> Arc<Self> {
+        let pool = Arc::new(Self {
+            idle: Mutex::new(HashMap::new()),
+            idle_timeout,
+            max_per_host,
+        });
+        Arc::clone(&pool).spawn_eviction_task();
+        pool
+    }
+
+    fn spawn_eviction_task(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = interval(self.idle_timeout);
+            loop {
+                ticker.tick().await;
+                self.evict_expired().await;
+            }
+        });
+    }
+
+    async fn evict_expired(&self) {
+        let mut idle = self.idle.lock().await;
+        let timeout = self.idle_timeout;
+        for conns in idle.values_mut() {
+            conns.retain(|c| c.idle_since.elapsed() < timeout);
+        }
+        idle.retain(|_, conns| !conns.is_empty());
+    }
+
+    /// Take a still-fresh idle connection for `(hs_addr, hs_port)`, if any.
+    pub async fn take(&self, hs_addr: &str, hs_port: u16) -> Option<PooledConn> {
+        let mut idle = self.idle.lock().await;
+        let conns = idle.get_mut(&(hs_addr.to_string(), hs_port))?;
+        while let Some(entry) = conns.pop_front() {
+            if entry.idle_since.elapsed() < self.idle_timeout {
+                return Some(entry.conn);
+            }
+        }
+        None
+    }
+
+    /// Return a connection to the pool for reuse. Dropped instead if the
+    /// host is already at `max_per_host`.
+    pub async fn put(&self, hs_addr: &str, hs_port: u16, conn: PooledConn) {
+        let mut idle = self.idle.lock().await;
+        let conns = idle
+            .entry((hs_addr.to_string(), hs_port))
+            .or_insert_with(VecDeque::new);
+        if conns.len() < self.max_per_host {
+            conns.push_back(IdleConn {
+                conn,
+                idle_since: Instant::now(),
+            });
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! A minimal HTTP/1.1 response reader shared by the onion-service client code.
+//!
+//! Both `TorHSClient` and the PyArti request helpers need to tell where a
+//! response's headers end and its body begins, honor `Content-Length` /
+//! `Transfer-Encoding: chunked` framing, and optionally undo whatever
+//! `Content-Encoding` the origin applied. This module is the one place that
+//! logic lives so the HTTP and HTTPS code paths can't drift apart.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result as AnyResult};
+use async_compression::tokio::bufread::{BrotliDecoder, DeflateDecoder, GzipDecoder, ZstdDecoder};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+/// Refuse to buffer a response body larger than this, mirroring the cap the
+/// old hand-rolled read loops used.
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// `Accept-Encoding` value `HttpRequest` advertises by default, matching the
+/// codecs `read_response` knows how to undo.
+pub const DEFAULT_ACCEPT_ENCODING: &str = "gzip, br, deflate, zstd";
+
+/// A builder for an HTTP/1.1 request.
+///
+/// `Host`, `Accept-Encoding`, and `Connection: keep-alive` are filled in with
+/// sensible defaults if the caller doesn't set them explicitly, so simple
+/// GETs don't need any boilerplate while POSTs and custom headers are still
+/// possible. Callers that want the connection closed after one response can
+/// still override `Connection` explicitly.
+pub struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+}
+
+impl HttpRequest {
+    /// Start building a `GET` request for `path`.
+    pub fn get(path: impl Into<String>) -> Self {
+        Self::new("GET", path)
+    }
+
+    /// Start building a request for `path` using an arbitrary `method`.
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Add a header, overriding any default the builder would otherwise add.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Attach a request body, sent with a matching `Content-Length`.
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Render the request as the bytes to write to the wire, filling in
+    /// `Host`, `Accept-Encoding`, and `Connection` defaults for `host` if the
+    /// caller didn't set them.
+    pub fn into_bytes(self, host: &str) -> Vec<u8> {
+        let has_header = |name: &str| {
+            self.headers
+                .iter()
+                .any(|(n, _)| n.eq_ignore_ascii_case(name))
+        };
+
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method, self.path);
+        if !has_header("host") {
+            head.push_str(&format!("Host: {}\r\n", host));
+        }
+        if !has_header("accept-encoding") {
+            head.push_str(&format!("Accept-Encoding: {}\r\n", DEFAULT_ACCEPT_ENCODING));
+        }
+        if !has_header("connection") {
+            head.push_str("Connection: keep-alive\r\n");
+        }
+        if let Some(body) = &self.body {
+            if !has_header("content-length") {
+                head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+            }
+        }
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        let mut bytes = head.into_bytes();
+        if let Some(body) = self.body {
+            bytes.extend_from_slice(&body);
+        }
+        bytes
+    }
+}
+
+/// A fully-read HTTP/1.1 response.
+pub struct HttpResponse {
+    /// The numeric status code, e.g. `200`.
+    pub status_code: u16,
+    /// The raw status line, e.g. `HTTP/1.1 200 OK`.
+    pub status_line: String,
+    /// Response headers, keyed by lower-cased header name.
+    pub headers: HashMap<String, String>,
+    /// The response body, decoded according to `Content-Encoding` unless the
+    /// caller asked for raw bytes.
+    pub body: Vec<u8>,
+}
+
+/// Read a single HTTP/1.1 response from `stream`.
+///
+/// When `decompress` is `true`, a `Content-Encoding` of `gzip`, `br`,
+/// `deflate`, or `zstd` is transparently undone; otherwise `body` keeps its
+/// content encoding. Chunked transfer framing is removed either way.
+pub async fn read_response<S>(stream: S, decompress: bool) -> AnyResult<HttpResponse>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(stream);
+    let (status_line, status_code, headers) = read_status_and_headers(&mut reader).await?;
+
+    let body = if let Some(len) = headers.get("content-length") {
+        let len: usize = len
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("invalid Content-Length header: {}", len))?;
+        read_exact_capped(&mut reader, len).await?
+    } else if headers
+        .get("transfer-encoding")
+        .map(|v| v.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false)
+    {
+        read_chunked(&mut reader).await?
+    } else {
+        read_to_eof_capped(&mut reader).await?
+    };
+
+    let body = if decompress {
+        decode_body(&headers, body).await?
+    } else {
+        body
+    };
+
+    Ok(HttpResponse {
+        status_code,
+        status_line,
+        headers,
+        body,
+    })
+}
+
+/// Read the status line and headers, returning the lower-cased header map.
+async fn read_status_and_headers<S>(
+    reader: &mut BufReader<S>,
+) -> AnyResult<(String, u16, HashMap<String, String>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).await?;
+    let status_line = status_line.trim_end().to_string();
+    if status_line.is_empty() {
+        return Err(anyhow!("connection closed before a response was received"));
+    }
+
+    let status_code = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("malformed status line: {}", status_line))?;
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    Ok((status_line, status_code, headers))
+}
+
+/// Read exactly `len` bytes of body, refusing to buffer more than
+/// [`MAX_BODY_BYTES`].
+async fn read_exact_capped<S>(reader: &mut BufReader<S>, len: usize) -> AnyResult<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    if len > MAX_BODY_BYTES {
+        return Err(anyhow!("Content-Length {} exceeds the {} byte cap", len, MAX_BODY_BYTES));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+/// Read until EOF, refusing to buffer more than [`MAX_BODY_BYTES`].
+async fn read_to_eof_capped<S>(reader: &mut BufReader<S>) -> AnyResult<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut body = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+        if body.len() > MAX_BODY_BYTES {
+            return Err(anyhow!("response body exceeds the {} byte cap", MAX_BODY_BYTES));
+        }
+    }
+    Ok(body)
+}
+
+/// Read a `Transfer-Encoding: chunked` body, dropping the trailing trailer
+/// headers.
+async fn read_chunked<S>(reader: &mut BufReader<S>) -> AnyResult<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line).await?;
+        let size_line = size_line.trim();
+        let size = usize::from_str_radix(
+            size_line.split(';').next().unwrap_or(""),
+            16,
+        )
+        .map_err(|_| anyhow!("malformed chunk size: {}", size_line))?;
+
+        if size == 0 {
+            // Consume the (possibly empty) trailer section.
+            loop {
+                let mut trailer_line = String::new();
+                reader.read_line(&mut trailer_line).await?;
+                if trailer_line.trim_end_matches(['\r', '\n']).is_empty() {
+                    break;
+                }
+            }
+            break;
+        }
+
+        if body.len() + size > MAX_BODY_BYTES {
+            return Err(anyhow!("chunked response body exceeds the {} byte cap", MAX_BODY_BYTES));
+        }
+
+        let mut chunk = vec![0u8; size];
+        reader.read_exact(&mut chunk).await?;
+        body.extend_from_slice(&chunk);
+
+        // Each chunk is followed by a trailing CRLF.
+        let mut crlf = [0u8; 2];
+        reader.read_exact(&mut crlf).await?;
+    }
+    Ok(body)
+}
+
+/// Undo whatever `Content-Encoding` the response declared, if any.
+async fn decode_body(headers: &HashMap<String, String>, body: Vec<u8>) -> AnyResult<Vec<u8>> {
+    let encoding = match headers.get("content-encoding") {
+        Some(encoding) => encoding.to_ascii_lowercase(),
+        None => return Ok(body),
+    };
+
+    let mut decoded = Vec::new();
+    match encoding.as_str() {
+        "gzip" | "x-gzip" => {
+            GzipDecoder::new(body.as_slice())
+                .read_to_end(&mut decoded)
+                .await?;
+        }
+        "br" => {
+            BrotliDecoder::new(body.as_slice())
+                .read_to_end(&mut decoded)
+                .await?;
+        }
+        "deflate" => {
+            DeflateDecoder::new(body.as_slice())
+                .read_to_end(&mut decoded)
+                .await?;
+        }
+        "zstd" => {
+            ZstdDecoder::new(body.as_slice())
+                .read_to_end(&mut decoded)
+                .await?;
+        }
+        "identity" | "" => return Ok(body),
+        other => return Err(anyhow!("unsupported Content-Encoding: {}", other)),
+    }
+
+    Ok(decoded)
+}
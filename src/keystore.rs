@@ -0,0 +1,122 @@
+//! A pluggable store for long-lived key material (client-authorization
+//! x25519 keys, onion-service credentials) keyed by an opaque `arti_path`
+//! string, so it can be passed into whatever subsystem needs to read or
+//! persist keys without that subsystem caring whether they live in memory
+//! or on disk.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result as AnyResult};
+
+/// A single stored key: its raw bytes plus the type tag it was inserted
+/// under, so a lookup under the wrong `item_type` can be rejected instead
+/// of silently handing back key material of the wrong kind.
+#[derive(Clone)]
+struct KeyEntry {
+    item_type: String,
+    material: Vec<u8>,
+}
+
+/// A keyed store of key material. Implemented first by [`EphemeralKeyStore`]
+/// (in-memory, lost on process exit); a disk-backed implementation can be
+/// swapped in later by anything that holds a `dyn KeyStore` rather than a
+/// concrete type.
+pub trait KeyStore: Send + Sync {
+    /// Whether a key is stored at `arti_path` under `item_type`.
+    fn contains(&self, arti_path: &str, item_type: &str) -> AnyResult<bool>;
+
+    /// Fetch the raw key material stored at `arti_path`, if any.
+    fn get(&self, arti_path: &str, item_type: &str) -> AnyResult<Option<Vec<u8>>>;
+
+    /// Store `material` at `arti_path` under `item_type`, overwriting
+    /// whatever was there before.
+    fn insert(&self, arti_path: &str, item_type: &str, material: &[u8]) -> AnyResult<()>;
+
+    /// Remove the key stored at `arti_path`, if any.
+    fn remove(&self, arti_path: &str, item_type: &str) -> AnyResult<Option<()>>;
+
+    /// List every `(arti_path, item_type)` pair currently stored.
+    fn list(&self) -> AnyResult<Vec<(String, String)>>;
+}
+
+/// An in-memory [`KeyStore`]: keys are never written to disk and don't
+/// survive the process exiting.
+#[derive(Default)]
+pub struct EphemeralKeyStore {
+    entries: Mutex<HashMap<String, KeyEntry>>,
+}
+
+impl EphemeralKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Verify that the entry found at `arti_path` reports itself as
+/// `requested`, so a caller can't be handed back key material of a
+/// different type than the one it asked for.
+fn check_item_type(arti_path: &str, entry: &KeyEntry, requested: &str) -> AnyResult<()> {
+    if entry.item_type != requested {
+        return Err(anyhow!(
+            "key at {} has type {}, not the requested {}",
+            arti_path,
+            entry.item_type,
+            requested
+        ));
+    }
+    Ok(())
+}
+
+impl KeyStore for EphemeralKeyStore {
+    fn contains(&self, arti_path: &str, item_type: &str) -> AnyResult<bool> {
+        let entries = self.entries.lock().expect("lock poisoned");
+        match entries.get(arti_path) {
+            Some(entry) => check_item_type(arti_path, entry, item_type).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    fn get(&self, arti_path: &str, item_type: &str) -> AnyResult<Option<Vec<u8>>> {
+        let entries = self.entries.lock().expect("lock poisoned");
+        match entries.get(arti_path) {
+            Some(entry) => {
+                check_item_type(arti_path, entry, item_type)?;
+                Ok(Some(entry.material.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn insert(&self, arti_path: &str, item_type: &str, material: &[u8]) -> AnyResult<()> {
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        entries.insert(
+            arti_path.to_string(),
+            KeyEntry {
+                item_type: item_type.to_string(),
+                material: material.to_vec(),
+            },
+        );
+        Ok(())
+    }
+
+    fn remove(&self, arti_path: &str, item_type: &str) -> AnyResult<Option<()>> {
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        match entries.get(arti_path) {
+            Some(entry) => {
+                check_item_type(arti_path, entry, item_type)?;
+                entries.remove(arti_path);
+                Ok(Some(()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn list(&self) -> AnyResult<Vec<(String, String)>> {
+        let entries = self.entries.lock().expect("lock poisoned");
+        Ok(entries
+            .iter()
+            .map(|(arti_path, entry)| (arti_path.clone(), entry.item_type.clone()))
+            .collect())
+    }
+}
@@ -2,6 +2,14 @@ mod tor_circmgr;
 mod tor_chanmgr;
 mod tor_hs_client;
 mod tor_hs_connector;
+mod error;
+mod http;
+mod transport;
+mod hs_pool;
+mod hs_path;
+mod keystore;
+mod socks_proxy;
+mod relay_diversity;
 
 mod test;
 
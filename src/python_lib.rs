@@ -2,23 +2,171 @@ mod tor_circmgr;
 mod tor_chanmgr;
 mod tor_hs_client;
 mod tor_hs_connector;
+mod error;
+mod http;
+mod transport;
+mod hs_pool;
+mod hs_path;
+mod keystore;
+mod socks_proxy;
+mod relay_diversity;
+mod python_lib_async_test;
 
-use tor_circmgr::TorCircuitManager;
+use tor_circmgr::{CongestionControl, TorCircuitManager};
 use tor_rtcompat::{BlockOn, PreferredRuntime};
 use tor_hs_client::TorHSClient;
+use keystore::{EphemeralKeyStore, KeyStore};
+use crate::http::HttpRequest;
 
 use log::info;
 use pyo3::prelude::*;
 use pyo3::exceptions::PyValueError;
+use pyo3::types::PyBytes;
 use std::collections::HashMap;
-use futures::{AsyncReadExt, AsyncWriteExt};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use tokio::sync::Mutex as AsyncMutex;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use futures_rustls::rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use futures_rustls::TlsConnector;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+/// Distinguishes a TLS handshake/certificate failure from a plain
+/// transport-level one (writing to or reading from the underlying stream),
+/// so callers of [`PyArtiClient::connect`] can tell a misconfigured or
+/// incompatible TLS origin apart from a Tor circuit/stream problem.
+enum ConnectError {
+    /// Failure at the Tor stream / TCP-equivalent layer.
+    Transport(String),
+    /// Failure establishing or validating the TLS session.
+    TlsHandshake(String),
+}
+
+impl From<ConnectError> for PyErr {
+    fn from(e: ConnectError) -> Self {
+        match e {
+            ConnectError::Transport(msg) => PyValueError::new_err(format!("Transport error: {}", msg)),
+            ConnectError::TlsHandshake(msg) => {
+                PyValueError::new_err(format!("TLS handshake failed: {}", msg))
+            }
+        }
+    }
+}
+
+/// A parsed, ready-to-use client certificate and private key for mutual TLS.
+struct ClientIdentity {
+    cert_chain: Vec<Certificate>,
+    private_key: PrivateKey,
+}
+
+/// Parse a PEM client certificate chain and private key into a
+/// [`ClientIdentity`], decrypting the key with `passphrase` first if one is
+/// given.
+fn load_client_identity(
+    cert_pem: &[u8],
+    key_pem: &[u8],
+    passphrase: Option<&str>,
+) -> PyResult<ClientIdentity> {
+    let cert_chain = rustls_pemfile::certs(&mut &cert_pem[..])
+        .map_err(|e| PyValueError::new_err(format!("Invalid client certificate PEM: {}", e)))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+
+    let private_key = match passphrase {
+        Some(passphrase) => {
+            let pkey =
+                openssl::pkey::PKey::private_key_from_pem_passphrase(key_pem, passphrase.as_bytes())
+                    .map_err(|e| {
+                        PyValueError::new_err(format!("Failed to decrypt client private key: {}", e))
+                    })?;
+            let der = pkey
+                .private_key_to_der()
+                .map_err(|e| PyValueError::new_err(format!("Invalid client private key: {}", e)))?;
+            PrivateKey(der)
+        }
+        None => {
+            let mut keys = rustls_pemfile::pkcs8_private_keys(&mut &key_pem[..])
+                .map_err(|e| PyValueError::new_err(format!("Invalid client private key PEM: {}", e)))?;
+            let key = keys
+                .pop()
+                .ok_or_else(|| PyValueError::new_err("No private key found in PEM"))?;
+            PrivateKey(key)
+        }
+    };
+
+    Ok(ClientIdentity {
+        cert_chain,
+        private_key,
+    })
+}
+
+/// Build a rustls client config trusting the Mozilla/webpki root set, with
+/// `client_identity` presented during the handshake if the origin requests
+/// one (mutual TLS).
+fn build_tls_config(client_identity: Option<ClientIdentity>) -> PyResult<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+
+    let config = match client_identity {
+        Some(identity) => builder
+            .with_client_auth_cert(identity.cert_chain, identity.private_key)
+            .map_err(|e| PyValueError::new_err(format!("Invalid client certificate/key: {}", e)))?,
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(config)
+}
+
+/// Write `request` to `stream` and read back the whole response as a string,
+/// generic over a plaintext or TLS-wrapped stream alike. A response that is
+/// not valid UTF-8 is reported as a transport error.
+async fn send_get_request<S>(stream: &mut S, request: &str) -> Result<String, ConnectError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| ConnectError::Transport(format!("failed to write request: {}", e)))?;
+
+    // IMPORTANT: Make sure the request was written.
+    // Arti buffers data, so flushing the buffer is usually required.
+    stream
+        .flush()
+        .await
+        .map_err(|e| ConnectError::Transport(format!("failed to flush stream: {}", e)))?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .map_err(|e| ConnectError::Transport(format!("failed to read response: {}", e)))?;
+
+    Ok(response)
+}
 
 
 #[pyclass]
 #[pyo3(text_signature = "()")]
 pub struct PyArtiClient {
     runtime: PreferredRuntime,
-    circ_manager: TorCircuitManager<PreferredRuntime>,
+    /// Shared behind an async mutex (rather than owned directly) so the
+    /// `*_async` methods can clone the `Arc` into a detached future instead
+    /// of borrowing `&self` across an `.await`, which `Bound<'py, PyAny>`
+    /// awaitables can't do.
+    circ_manager: Arc<AsyncMutex<TorCircuitManager<PreferredRuntime>>>,
 }
 
 #[pymethods]
@@ -26,119 +174,545 @@ impl PyArtiClient {
     #[new]
     fn new() -> PyResult<Self> {
         let runtime = PreferredRuntime::create()?;
-        let circ_manager = TorCircuitManager::new(runtime.clone())
+        let circ_manager = TorCircuitManager::new(runtime.clone(), CongestionControl::default())
         .map_err(|e| PyValueError::new_err(format!("Failed to create circuit manager: {}", e)))?;
 
-        Ok(Self { runtime, circ_manager })
+        Ok(Self { runtime, circ_manager: Arc::new(AsyncMutex::new(circ_manager)) })
     }
 
     #[pyo3(text_signature = "()")]
-    fn init(&self) -> PyResult<()> {
-        self.runtime.block_on(async {
-            self.circ_manager.init().await
-                .map_err(|e| PyValueError::new_err(format!("Initialization failed: {}", e)))
+    fn init(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                self.circ_manager.lock().await.init().await
+                    .map_err(|e| PyValueError::new_err(format!("Initialization failed: {}", e)))
+            })
         })
     }
 
     #[pyo3(text_signature = "(relay_ip, relay_port, rsa_id)")]
     fn create(
         &mut self,
+        py: Python<'_>,
         relay_ip: &str,
         relay_port: u16,
         rsa_id: &str,
     ) -> PyResult<()> {
-        self.runtime.block_on(async {
-            match self.circ_manager.create(
-                relay_ip,
-                relay_port,
-                rsa_id,
-            ).await {
-                Ok(_) => {
-                    info!("Created the firsthop circuit.");
-
-                    Ok(())
-                },
-                Err(e) => Err(PyValueError::new_err(format!("Connection failed: {}", e)))
-            }
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                match self.circ_manager.lock().await.create(
+                    relay_ip,
+                    relay_port,
+                    rsa_id,
+                ).await {
+                    Ok(_) => {
+                        info!("Created the firsthop circuit.");
+
+                        Ok(())
+                    },
+                    Err(e) => Err(PyValueError::new_err(format!("Connection failed: {}", e)))
+                }
+            })
+        })
+    }
+
+    /// Awaitable counterpart to [`Self::create`]: releases the GIL while the
+    /// first-hop circuit is built, so the calling asyncio loop stays
+    /// responsive and other circuits can progress concurrently.
+    ///
+    /// Driven by `pyo3_asyncio::tokio::future_into_py` on its own Tokio
+    /// runtime, separate from the `PreferredRuntime` held in `circ_manager`.
+    /// That's fine here: the future only awaits `circ_manager`'s
+    /// `tokio::sync::Mutex` (runtime-agnostic) before handing off to
+    /// `TorCircuitManager`, which always drives its own work through the
+    /// `PreferredRuntime` it was constructed with, regardless of which
+    /// runtime polls this outer future. See
+    /// [`crate::python_lib_async_test::test_async_bridge`] for a check that
+    /// this actually resolves instead of hanging or panicking.
+    #[pyo3(text_signature = "(relay_ip, relay_port, rsa_id)")]
+    fn create_async<'py>(
+        &self,
+        py: Python<'py>,
+        relay_ip: String,
+        relay_port: u16,
+        rsa_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let circ_manager = self.circ_manager.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            circ_manager
+                .lock()
+                .await
+                .create(&relay_ip, relay_port, &rsa_id)
+                .await
+                .map_err(|e| PyValueError::new_err(format!("Connection failed: {}", e)))?;
+
+            info!("Created the firsthop circuit.");
+
+            Ok(())
         })
     }
 
     #[pyo3(text_signature = "(relay_ip, relay_port, rsa_id)")]
     fn extend(
         &mut self,
+        py: Python<'_>,
         relay_ip: &str,
         relay_port: u16,
         rsa_id: &str,
     ) -> PyResult<()> {
-        self.runtime.block_on(async {
-            match self.circ_manager.extend(
-                relay_ip,
-                relay_port,
-                rsa_id,
-            ).await {
-                Ok(_) => {
-                    info!("Extended the circuit.");
-
-                    Ok(())
-                },
-                Err(e) => Err(PyValueError::new_err(format!("Connection failed: {}", e)))
-            }
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                match self.circ_manager.lock().await.extend(
+                    relay_ip,
+                    relay_port,
+                    rsa_id,
+                ).await {
+                    Ok(_) => {
+                        info!("Extended the circuit.");
+
+                        Ok(())
+                    },
+                    Err(e) => Err(PyValueError::new_err(format!("Connection failed: {}", e)))
+                }
+            })
+        })
+    }
+
+    /// Awaitable counterpart to [`Self::extend`].
+    #[pyo3(text_signature = "(relay_ip, relay_port, rsa_id)")]
+    fn extend_async<'py>(
+        &self,
+        py: Python<'py>,
+        relay_ip: String,
+        relay_port: u16,
+        rsa_id: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let circ_manager = self.circ_manager.clone();
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            circ_manager
+                .lock()
+                .await
+                .extend(&relay_ip, relay_port, &rsa_id)
+                .await
+                .map_err(|e| PyValueError::new_err(format!("Connection failed: {}", e)))?;
+
+            info!("Extended the circuit.");
+
+            Ok(())
+        })
+    }
+
+    /// Fetch `url` (scheme `http` or `https`) over the established circuit.
+    ///
+    /// For `https`, the `DataStream` is wrapped in a TLS session (SNI'd to
+    /// `host`) before the request is written. `client_cert_pem`/
+    /// `client_key_pem` (both required together) enable mutual TLS;
+    /// `client_key_passphrase` decrypts the key first if it's encrypted.
+    #[pyo3(text_signature = "(url, port, client_cert_pem=None, client_key_pem=None, client_key_passphrase=None)")]
+    fn connect(
+        &self,
+        py: Python<'_>,
+        url: &str,
+        port: u16,
+        client_cert_pem: Option<&str>,
+        client_key_pem: Option<&str>,
+        client_key_passphrase: Option<&str>,
+    ) -> PyResult<String> {
+        let (host, path, is_https) = parse_http_url(url)?;
+        let client_identity = build_client_identity(client_cert_pem, client_key_pem, client_key_passphrase)?;
+
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let client_circ = self.circ_manager.lock().await.get_circ()
+                    .map_err(|_| PyValueError::new_err("No circuit exists"))?;
+
+                fetch_over_circuit(&client_circ, &host, port, &path, is_https, client_identity).await
+            })
         })
     }
 
-    #[pyo3(text_signature = "(url, port)")]
-    fn connect(&self, url: &str, port: u16) -> PyResult<String> {
-        let (_, rest) = url.split_once("://")
-            .ok_or_else(|| PyValueError::new_err("Invalid URL: Missing scheme (http or https)"))?;
-    
-        let (host, path) = match rest.split_once('/') {
-            Some((host, path)) => (host, format!("/{}", path)),
-            None => (rest, "/".to_string()),
-        };
-    
-        self.runtime.block_on(async {
-            let client_circ = self.circ_manager.get_circ()
+    /// Awaitable counterpart to [`Self::connect`].
+    #[pyo3(text_signature = "(url, port, client_cert_pem=None, client_key_pem=None, client_key_passphrase=None)")]
+    fn connect_async<'py>(
+        &self,
+        py: Python<'py>,
+        url: String,
+        port: u16,
+        client_cert_pem: Option<String>,
+        client_key_pem: Option<String>,
+        client_key_passphrase: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let (host, path, is_https) = parse_http_url(&url)?;
+        let client_identity = build_client_identity(
+            client_cert_pem.as_deref(),
+            client_key_pem.as_deref(),
+            client_key_passphrase.as_deref(),
+        )?;
+        let circ_manager = self.circ_manager.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let client_circ = circ_manager
+                .lock()
+                .await
+                .get_circ()
                 .map_err(|_| PyValueError::new_err("No circuit exists"))?;
-    
-            let request = format!(
-                "GET {} HTTP/1.1\r\n\
-                    Host: {}\r\n\
-                    Connection: close\r\n\
-                    \r\n",
-                path, host
-            );
-    
-            let mut stream = match client_circ.begin_stream(host, port, None).await {
-                Ok(stream) => stream,
-                Err(e) => return Err(PyValueError::new_err(format!("Failed to begin stream: {}", e))),
-            };
-    
-            // Write request to the stream
-            stream.write_all(request.as_bytes()).await.map_err(|e| {
-                PyValueError::new_err(format!("Connection failed to write request: {}", e))
-            })?;
-    
-            // IMPORTANT: Make sure the request was written.
-            // Arti buffers data, so flushing the buffer is usually required.
-            stream.flush().await.map_err(|e| {
-                PyValueError::new_err(format!("Failed to flush stream: {}", e))
-            })?;
-    
-            // Read the response into a string
-            let mut response = String::new();
-            match stream.read_to_string(&mut response).await {
-                Ok(_) => Ok(response),
-                Err(e) => Err(PyValueError::new_err(format!("Failed to read response: {}", e))),
-            }
+
+            fetch_over_circuit(&client_circ, &host, port, &path, is_https, client_identity).await
+        })
+    }
+
+    /// Open a raw stream to `host:port` over the established circuit,
+    /// optionally wrapped in TLS, for callers that need to send something
+    /// other than a one-shot `GET` (arbitrary methods, request bodies,
+    /// streamed/binary responses, keep-alive).
+    #[pyo3(signature = (host, port, tls=false, client_cert_pem=None, client_key_pem=None, client_key_passphrase=None))]
+    #[pyo3(text_signature = "(host, port, tls=False, client_cert_pem=None, client_key_pem=None, client_key_passphrase=None)")]
+    fn open_stream(
+        &self,
+        py: Python<'_>,
+        host: &str,
+        port: u16,
+        tls: bool,
+        client_cert_pem: Option<&str>,
+        client_key_pem: Option<&str>,
+        client_key_passphrase: Option<&str>,
+    ) -> PyResult<PyArtiStream> {
+        let client_identity = build_client_identity(client_cert_pem, client_key_pem, client_key_passphrase)?;
+
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let client_circ = self.circ_manager.lock().await.get_circ()
+                    .map_err(|_| PyValueError::new_err("No circuit exists"))?;
+
+                let stream = open_raw_stream(&client_circ, host, port, tls, client_identity).await?;
+
+                Ok(PyArtiStream {
+                    runtime: self.runtime.clone(),
+                    inner: Arc::new(AsyncMutex::new(stream)),
+                })
+            })
         })
     }
+
+    /// Send a `method` request to `url` with `headers`/`body`, returning
+    /// `(status_code, headers, body)` separately rather than assuming a
+    /// UTF-8 `GET` response the way [`Self::connect`] does.
+    #[pyo3(text_signature = "(method, url, port, headers=None, body=None, client_cert_pem=None, client_key_pem=None, client_key_passphrase=None)")]
+    #[allow(clippy::too_many_arguments)]
+    fn request(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        url: &str,
+        port: u16,
+        headers: Option<HashMap<String, String>>,
+        body: Option<Vec<u8>>,
+        client_cert_pem: Option<&str>,
+        client_key_pem: Option<&str>,
+        client_key_passphrase: Option<&str>,
+    ) -> PyResult<(u16, HashMap<String, String>, Py<PyBytes>)> {
+        let (host, path, is_https) = parse_http_url(url)?;
+        let client_identity = build_client_identity(client_cert_pem, client_key_pem, client_key_passphrase)?;
+
+        let (status_code, resp_headers, resp_body) = py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let client_circ = self.circ_manager.lock().await.get_circ()
+                    .map_err(|_| PyValueError::new_err("No circuit exists"))?;
+
+                let mut stream = open_raw_stream(&client_circ, &host, port, is_https, client_identity).await?;
+
+                let mut request = HttpRequest::new(method, path.clone()).header("Connection", "close");
+                for (name, value) in headers.unwrap_or_default() {
+                    request = request.header(name, value);
+                }
+                if let Some(body) = body {
+                    request = request.body(body);
+                }
+                let request = request.into_bytes(&host);
+
+                stream.write_all(&request).await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to write request: {}", e)))?;
+                stream.flush().await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to flush request: {}", e)))?;
+
+                // Adapt the futures-io stream to tokio-io so the response can
+                // be read with `http::read_response`'s Content-Length/
+                // chunked/EOF framing logic instead of duplicating it here —
+                // the hand-rolled reader that used to live in this function
+                // never understood `Transfer-Encoding: chunked`.
+                let response = http::read_response(stream.compat(), false).await
+                    .map_err(|e| PyValueError::new_err(format!("Error reading HTTP response: {}", e)))?;
+
+                Ok::<_, PyErr>((response.status_code, response.headers, response.body))
+            })
+        })?;
+
+        Ok((status_code, resp_headers, PyBytes::new_bound(py, &resp_body).unbind()))
+    }
+}
+
+/// Split `url` into `(host, path, is_https)`.
+fn parse_http_url(url: &str) -> PyResult<(String, String, bool)> {
+    let (scheme, rest) = url.split_once("://")
+        .ok_or_else(|| PyValueError::new_err("Invalid URL: Missing scheme (http or https)"))?;
+
+    let is_https = match scheme {
+        "http" => false,
+        "https" => true,
+        _ => return Err(PyValueError::new_err(format!("Unsupported URL scheme: {}", scheme))),
+    };
+
+    let (host, path) = match rest.split_once('/') {
+        Some((host, path)) => (host.to_string(), format!("/{}", path)),
+        None => (rest.to_string(), "/".to_string()),
+    };
+
+    Ok((host, path, is_https))
+}
+
+/// Parse an optional client cert/key pair into a [`ClientIdentity`],
+/// enforcing that both (or neither) are given.
+fn build_client_identity(
+    cert_pem: Option<&str>,
+    key_pem: Option<&str>,
+    passphrase: Option<&str>,
+) -> PyResult<Option<ClientIdentity>> {
+    match (cert_pem, key_pem) {
+        (Some(cert), Some(key)) => {
+            Ok(Some(load_client_identity(cert.as_bytes(), key.as_bytes(), passphrase)?))
+        }
+        (None, None) => Ok(None),
+        _ => Err(PyValueError::new_err(
+            "client_cert_pem and client_key_pem must both be provided for mutual TLS",
+        )),
+    }
+}
+
+/// Begin a stream to `host:port` over `client_circ`, wrap it in TLS when
+/// `is_https`, send a bare `GET path`, and return the whole raw response
+/// (status line, headers and body).
+async fn fetch_over_circuit(
+    client_circ: &tor_proto::circuit::ClientCirc,
+    host: &str,
+    port: u16,
+    path: &str,
+    is_https: bool,
+    client_identity: Option<ClientIdentity>,
+) -> PyResult<String> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+            Host: {}\r\n\
+            Connection: close\r\n\
+            \r\n",
+        path, host
+    );
+
+    let stream = client_circ.begin_stream(host, port, None).await
+        .map_err(|e| PyValueError::new_err(format!("Failed to begin stream: {}", e)))?;
+
+    if is_https {
+        let config = build_tls_config(client_identity)?;
+        let connector = TlsConnector::from(Arc::new(config));
+        let server_name = ServerName::try_from(host)
+            .map_err(|_| PyValueError::new_err(format!("Invalid DNS name: {}", host)))?;
+
+        let mut tls_stream = connector.connect(server_name, stream).await.map_err(|e| {
+            ConnectError::TlsHandshake(e.to_string())
+        })?;
+
+        Ok(send_get_request(&mut tls_stream, &request).await?)
+    } else {
+        let mut stream = stream;
+        Ok(send_get_request(&mut stream, &request).await?)
+    }
+}
+
+/// Erases whether a stream is a bare [`begin_stream`](tor_proto::circuit::ClientCirc::begin_stream)
+/// result or one wrapped in TLS, so [`PyArtiStream`] and [`request`](PyArtiClient::request)
+/// don't need to carry the wrapper type as a type parameter.
+trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+/// Begin a stream to `host:port` over `client_circ`, wrapping it in TLS when
+/// `tls` is set, for callers that need more than [`fetch_over_circuit`]'s
+/// one-shot `GET`.
+async fn open_raw_stream(
+    client_circ: &tor_proto::circuit::ClientCirc,
+    host: &str,
+    port: u16,
+    tls: bool,
+    client_identity: Option<ClientIdentity>,
+) -> PyResult<Box<dyn AsyncStream>> {
+    let stream = client_circ.begin_stream(host, port, None).await
+        .map_err(|e| PyValueError::new_err(format!("Failed to begin stream: {}", e)))?;
+
+    if tls {
+        let config = build_tls_config(client_identity)?;
+        let connector = TlsConnector::from(Arc::new(config));
+        let server_name = ServerName::try_from(host)
+            .map_err(|_| PyValueError::new_err(format!("Invalid DNS name: {}", host)))?;
+
+        let tls_stream = connector.connect(server_name, stream).await.map_err(|e| {
+            ConnectError::TlsHandshake(e.to_string())
+        })?;
+
+        Ok(Box::new(tls_stream))
+    } else {
+        Ok(Box::new(stream))
+    }
+}
+
+/// A stream opened by [`PyArtiClient::open_stream`], for Python code that
+/// needs to send arbitrary methods/bodies or read a large or non-UTF-8
+/// response without buffering it all through [`PyArtiClient::connect`].
+#[pyclass]
+pub struct PyArtiStream {
+    runtime: PreferredRuntime,
+    inner: Arc<AsyncMutex<Box<dyn AsyncStream>>>,
+}
+
+#[pymethods]
+impl PyArtiStream {
+    #[pyo3(text_signature = "(data)")]
+    fn write(&self, py: Python<'_>, data: Vec<u8>) -> PyResult<()> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let mut inner = self.inner.lock().await;
+                inner.write_all(&data).await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to write to stream: {}", e)))?;
+                inner.flush().await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to flush stream: {}", e)))
+            })
+        })
+    }
+
+    /// Read up to `n` bytes, returning fewer if that's all that's
+    /// currently available (like a POSIX socket read, not `read_exact`).
+    #[pyo3(text_signature = "(n)")]
+    fn read(&self, py: Python<'_>, n: usize) -> PyResult<Py<PyBytes>> {
+        let bytes = py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let mut inner = self.inner.lock().await;
+                let mut buf = vec![0u8; n];
+                let read = inner.read(&mut buf).await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to read from stream: {}", e)))?;
+                buf.truncate(read);
+                Ok::<_, PyErr>(buf)
+            })
+        })?;
+
+        Ok(PyBytes::new_bound(py, &bytes).unbind())
+    }
+
+    #[pyo3(text_signature = "()")]
+    fn read_to_end(&self, py: Python<'_>) -> PyResult<Py<PyBytes>> {
+        let bytes = py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let mut inner = self.inner.lock().await;
+                let mut buf = Vec::new();
+                inner.read_to_end(&mut buf).await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to read from stream: {}", e)))?;
+                Ok::<_, PyErr>(buf)
+            })
+        })?;
+
+        Ok(PyBytes::new_bound(py, &bytes).unbind())
+    }
+
+    #[pyo3(text_signature = "()")]
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                self.inner.lock().await.close().await
+                    .map_err(|e| PyValueError::new_err(format!("Failed to close stream: {}", e)))
+            })
+        })
+    }
+}
+
+/// A Python handle onto a [`KeyStore`], so client-authorization x25519 keys
+/// and onion-service credentials can be loaded from and persisted by Python
+/// code rather than an opaque string map. Backed by this crate's own
+/// in-memory [`EphemeralKeyStore`], not `tor-keymgr`'s typed
+/// `ArtiEphemeralKeystore` -- that crate's `ArtiPath`/`KeySpecifier`
+/// abstraction isn't part of this binary's dependency graph, and the opaque
+/// `(arti_path, item_type)` string pairs `KeyStore` already uses are a
+/// better fit for a Python-facing API anyway. Any other `KeyStore`
+/// implementation (e.g. a disk-backed one) can be swapped in behind the
+/// same `Arc<dyn KeyStore>` without changing this wrapper.
+#[pyclass]
+#[pyo3(text_signature = "()")]
+pub struct PyArtiKeystore {
+    store: Arc<dyn KeyStore>,
+}
+
+#[pymethods]
+impl PyArtiKeystore {
+    #[new]
+    fn new() -> Self {
+        Self {
+            store: Arc::new(EphemeralKeyStore::new()),
+        }
+    }
+
+    #[pyo3(text_signature = "(arti_path, item_type)")]
+    fn contains(&self, arti_path: &str, item_type: &str) -> PyResult<bool> {
+        self.store
+            .contains(arti_path, item_type)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    /// Fetch the key stored at `arti_path`, base64-encoded, or `None` if
+    /// there isn't one.
+    #[pyo3(text_signature = "(arti_path, item_type)")]
+    fn get(&self, arti_path: &str, item_type: &str) -> PyResult<Option<String>> {
+        let material = self
+            .store
+            .get(arti_path, item_type)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+
+        Ok(material.map(|bytes| STANDARD.encode(bytes)))
+    }
+
+    /// Store `material_b64` (base64-encoded key bytes; PEM material must
+    /// itself be base64-encoded, and is then kept as opaque bytes) at
+    /// `arti_path` under `item_type`.
+    #[pyo3(text_signature = "(arti_path, item_type, material_b64)")]
+    fn insert(&self, arti_path: &str, item_type: &str, material_b64: &str) -> PyResult<()> {
+        let material = STANDARD
+            .decode(material_b64)
+            .map_err(|e| PyValueError::new_err(format!("Invalid base64 key material: {}", e)))?;
+
+        self.store
+            .insert(arti_path, item_type, &material)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    #[pyo3(text_signature = "(arti_path, item_type)")]
+    fn remove(&self, arti_path: &str, item_type: &str) -> PyResult<bool> {
+        self.store
+            .remove(arti_path, item_type)
+            .map(|removed| removed.is_some())
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    /// List every `(arti_path, item_type)` pair currently stored.
+    #[pyo3(text_signature = "()")]
+    fn list(&self) -> PyResult<Vec<(String, String)>> {
+        self.store
+            .list()
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
 }
 
 #[pyclass]
 #[pyo3(text_signature = "()")]
 pub struct PyArtiHSClient {
     runtime: PreferredRuntime,
-    hs_client: TorHSClient,
+    hs_client: Arc<AsyncMutex<TorHSClient>>,
+    /// Holds client-authorization and onion-service keys across calls, set
+    /// via [`Self::init`]. `None` until a keystore has been supplied.
+    keystore: Option<Arc<dyn KeyStore>>,
 }
 
 #[pymethods]
@@ -151,50 +725,101 @@ impl PyArtiHSClient {
 
         Ok(Self {
             runtime,
-            hs_client
+            hs_client: Arc::new(AsyncMutex::new(hs_client)),
+            keystore: None,
         })
     }
 
-    #[pyo3(text_signature = "()")]
-    fn init(&mut self,  storage: Option<HashMap<String, String>>) -> PyResult<()> {
-        self.runtime.block_on(async {
-            let storage_ref = storage.as_ref().map(|s| s);
-            self.hs_client.init(storage_ref).await
-                .map_err(|e| PyValueError::new_err(format!("Initialization failed: {}", e)))
+    #[pyo3(text_signature = "(storage=None, keystore=None)")]
+    fn init(
+        &mut self,
+        py: Python<'_>,
+        storage: Option<HashMap<String, String>>,
+        keystore: Option<PyRef<PyArtiKeystore>>,
+    ) -> PyResult<()> {
+        if let Some(keystore) = keystore {
+            self.keystore = Some(keystore.store.clone());
+        }
+        let keystore = self.keystore.clone();
+
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let storage_ref = storage.as_ref();
+                let mut hs_client = self.hs_client.lock().await;
+                let init_result = match keystore {
+                    Some(keystore) => hs_client.init_with_keystore(storage_ref, keystore).await,
+                    None => hs_client.init(storage_ref).await,
+                };
+                init_result.map_err(|e| {
+                    e.report();
+                    PyValueError::new_err(format!("Initialization failed: {}", e))
+                })
+            })
         })
     }
 
     #[pyo3(text_signature = "()")]
     fn set_custom_hs_relay_ids(
         &self,
+        py: Python<'_>,
         guard_rsa_id: &str,
         middle_rsa_id: &str,
         exit_rsa_id: &str,
     ) -> PyResult<()> {
-        self.hs_client.set_custom_hs_relay_ids(
-            guard_rsa_id,
-            middle_rsa_id,
-            exit_rsa_id,
-        ).map_err(|e| PyValueError::new_err(format!("Failed to set custom relay ids: {}", e)))?;
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                self.hs_client.lock().await.set_custom_hs_relay_ids(
+                    guard_rsa_id,
+                    middle_rsa_id,
+                    exit_rsa_id,
+                ).map_err(|e| PyValueError::new_err(format!("Failed to set custom relay ids: {}", e)))
+            })
+        })?;
 
         Ok(())
     }
 
     #[pyo3(text_signature = "(hs_addr, hs_port)")]
-    fn connect(&self, hs_addr: &str, hs_port: u16) -> PyResult<String> {
-        self.runtime.block_on(async {
-            self.hs_client.connect_to_hs(hs_addr, hs_port).await
-                .map_err(|e| PyValueError::new_err(format!("Request failed failed: {}", e)))
+    fn connect(&self, py: Python<'_>, hs_addr: &str, hs_port: u16) -> PyResult<String> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                self.hs_client.lock().await.connect_to_hs(hs_addr, hs_port).await.map_err(|e| {
+                    e.report();
+                    PyValueError::new_err(format!("Request failed: {}", e))
+                })
+            })
+        })
+    }
+
+    /// Non-blocking variant of [`Self::connect`]: returns a Python awaitable
+    /// that resolves to the response body, without holding the GIL while the
+    /// request is in flight.
+    #[pyo3(text_signature = "(hs_addr, hs_port)")]
+    fn connect_to_hs_async<'py>(
+        &self,
+        py: Python<'py>,
+        hs_addr: String,
+        hs_port: u16,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let hs_client = self.hs_client.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            hs_client.lock().await.connect_to_hs(&hs_addr, hs_port).await.map_err(|e| {
+                e.report();
+                PyValueError::new_err(format!("Request failed: {}", e))
+            })
         })
     }
 }
 
 
 #[pymodule]
-fn pyarti(_py: Python, m: &PyModule) -> PyResult<()> {
+fn pyarti(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     env_logger::init();
     m.add_class::<PyArtiClient>()?;
     m.add_class::<PyArtiHSClient>()?;
-    m.add("__all__", vec!["PyArtiClient", "PyArtiHSClient"])?;
+    m.add_class::<PyArtiKeystore>()?;
+    m.add_class::<PyArtiStream>()?;
+    m.add("__all__", vec!["PyArtiClient", "PyArtiHSClient", "PyArtiKeystore", "PyArtiStream"])?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,29 @@
+//! Path-diversity checks shared by anything that samples relays for a
+//! circuit ([`TorHSConnector`](crate::tor_hs_connector::TorHSConnector) and
+//! [`TorCircuitManager`](crate::tor_circmgr::TorCircuitManager)), so the two
+//! don't drift apart on what "too close together" means.
+
+use std::net::IpAddr;
+
+use tor_netdir::Relay;
+
+/// Whether `a` and `b` share a /16 on any pair of their IPv4 addresses, a
+/// cheap stand-in for Tor's subnet-diversity rule when picking hops for a
+/// path. Checks every address on each relay, not just the first, so a
+/// shared IPv4 ORPort isn't missed just because one relay lists an IPv6
+/// address first.
+pub fn same_slash16(a: &Relay<'_>, b: &Relay<'_>) -> bool {
+    a.addrs().iter().any(|a_addr| {
+        b.addrs().iter().any(|b_addr| match (a_addr.ip(), b_addr.ip()) {
+            (IpAddr::V4(a4), IpAddr::V4(b4)) => a4.octets()[..2] == b4.octets()[..2],
+            _ => false,
+        })
+    })
+}
+
+/// Whether `a` and `b` are the same relay, or either one lists the other
+/// in the `family` entry of its microdescriptor — the
+/// actual diversity rule `exclude_same_family` is meant to enforce.
+pub fn same_family(a: &Relay<'_>, b: &Relay<'_>) -> bool {
+    a.rsa_id() == b.rsa_id() || a.family().contains(b.rsa_id()) || b.family().contains(a.rsa_id())
+}
@@ -0,0 +1,266 @@
+//! A local SOCKS5 front-end over [`TorCircuitManager`]'s established
+//! circuit, so any SOCKS-aware client application can use this crate as a
+//! proxy instead of going through the hardcoded HTTP `connect`/`request`
+//! helpers.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result as AnyResult};
+use futures::io::copy;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use log::{info, warn};
+use tor_rtcompat::Runtime;
+
+use crate::tor_circmgr::TorCircuitManager;
+
+/// SOCKS protocol version byte, per RFC 1928.
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_NO_AUTH: u8 = 0x00;
+const AUTH_NO_ACCEPTABLE: u8 = 0xFF;
+
+/// SOCKS5 `CMD` values this proxy understands.
+mod cmd {
+    pub const CONNECT: u8 = 0x01;
+    /// Tor's SOCKS extension for resolving a hostname to an address
+    /// without opening a connection.
+    pub const RESOLVE: u8 = 0xF0;
+}
+
+/// SOCKS5 `ATYP` values.
+mod atyp {
+    pub const IPV4: u8 = 0x01;
+    pub const DOMAIN: u8 = 0x03;
+    pub const IPV6: u8 = 0x04;
+}
+
+/// SOCKS5 reply codes (the subset this proxy ever sends).
+mod reply {
+    pub const SUCCEEDED: u8 = 0x00;
+    pub const COMMAND_NOT_SUPPORTED: u8 = 0x07;
+    pub const HOST_UNREACHABLE: u8 = 0x04;
+}
+
+/// A target address parsed out of a SOCKS5 request's `ATYP`/`DST.ADDR`.
+enum Target {
+    Addr(IpAddr),
+    Domain(String),
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Addr(addr) => write!(f, "{}", addr),
+            Target::Domain(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A SOCKS5 server that forwards every accepted connection over a
+/// [`TorCircuitManager`]'s established circuit.
+pub struct SocksProxy<R: Runtime> {
+    runtime: R,
+    circ_mgr: Arc<TorCircuitManager<R>>,
+}
+
+impl<R: Runtime> SocksProxy<R> {
+    pub fn new(runtime: R, circ_mgr: Arc<TorCircuitManager<R>>) -> Self {
+        Self { runtime, circ_mgr }
+    }
+
+    /// Bind `listen_addr` and serve SOCKS5 connections until the listener
+    /// errors. Each accepted connection is handled on its own spawned task
+    /// so one slow client can't block the others.
+    pub async fn run(&self, listen_addr: SocketAddr) -> AnyResult<()> {
+        let listener = self.runtime.listen(&listen_addr).await
+            .map_err(|e| anyhow!("Failed to bind SOCKS listener on {}: {}", listen_addr, e))?;
+
+        info!("SOCKS5 proxy listening on {}", listen_addr);
+
+        loop {
+            let (stream, peer) = listener.accept().await
+                .map_err(|e| anyhow!("Failed to accept SOCKS connection: {}", e))?;
+
+            let circ_mgr = self.circ_mgr.clone();
+            let spawned = self.runtime.spawn(async move {
+                if let Err(e) = handle_conn(stream, circ_mgr).await {
+                    warn!("SOCKS connection from {} failed: {}", peer, e);
+                }
+            });
+
+            if let Err(e) = spawned {
+                warn!("Failed to spawn handler for {}: {}", peer, e);
+            }
+        }
+    }
+}
+
+/// Handle one SOCKS5 client: negotiate auth, read its request, and either
+/// pump bytes between it and a Tor stream (`CONNECT`) or resolve a name
+/// over the circuit and reply with the address (`RESOLVE`).
+async fn handle_conn<R, S>(mut client: S, circ_mgr: Arc<TorCircuitManager<R>>) -> AnyResult<()>
+where
+    R: Runtime,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    negotiate_auth(&mut client).await?;
+    let (command, target, port) = read_request(&mut client).await?;
+
+    match command {
+        cmd::CONNECT => {
+            let client_circ = circ_mgr.get_circ()
+                .map_err(|e| anyhow!("No circuit available: {}", e))?;
+            let host = target.to_string();
+
+            let tor_stream = match client_circ.begin_stream(&host, port, None).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    write_addr_reply(&mut client, reply::HOST_UNREACHABLE).await?;
+                    return Err(anyhow!("Failed to begin stream to {}:{}: {}", host, port, e));
+                }
+            };
+
+            write_addr_reply(&mut client, reply::SUCCEEDED).await?;
+            pump(client, tor_stream).await
+        }
+        cmd::RESOLVE => {
+            let host = target.to_string();
+            let client_circ = circ_mgr.get_circ()
+                .map_err(|e| anyhow!("No circuit available: {}", e))?;
+
+            match client_circ.resolve(&host).await {
+                Ok(addrs) => {
+                    let addr = addrs.into_iter().next()
+                        .ok_or_else(|| anyhow!("No addresses returned for {}", host))?;
+                    write_resolved_reply(&mut client, addr).await
+                }
+                Err(e) => {
+                    write_addr_reply(&mut client, reply::HOST_UNREACHABLE).await?;
+                    Err(anyhow!("Failed to resolve {}: {}", host, e))
+                }
+            }
+        }
+        other => {
+            write_addr_reply(&mut client, reply::COMMAND_NOT_SUPPORTED).await?;
+            Err(anyhow!("Unsupported SOCKS command: 0x{:02x}", other))
+        }
+    }
+}
+
+/// Negotiate the no-authentication method, the only one this proxy offers.
+async fn negotiate_auth<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> AnyResult<()> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != SOCKS_VERSION {
+        return Err(anyhow!("unsupported SOCKS version: {}", header[0]));
+    }
+
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+
+    if methods.contains(&AUTH_NO_AUTH) {
+        stream.write_all(&[SOCKS_VERSION, AUTH_NO_AUTH]).await?;
+        stream.flush().await?;
+        Ok(())
+    } else {
+        stream.write_all(&[SOCKS_VERSION, AUTH_NO_ACCEPTABLE]).await?;
+        stream.flush().await?;
+        Err(anyhow!("client offered no acceptable authentication method"))
+    }
+}
+
+/// Read a SOCKS5 request's `VER CMD RSV ATYP DST.ADDR DST.PORT`.
+async fn read_request<S: AsyncRead + Unpin>(stream: &mut S) -> AnyResult<(u8, Target, u16)> {
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    let (version, command, atyp) = (head[0], head[1], head[3]);
+    if version != SOCKS_VERSION {
+        return Err(anyhow!("unsupported SOCKS version: {}", version));
+    }
+
+    let target = match atyp {
+        atyp::IPV4 => {
+            let mut octets = [0u8; 4];
+            stream.read_exact(&mut octets).await?;
+            Target::Addr(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        atyp::IPV6 => {
+            let mut octets = [0u8; 16];
+            stream.read_exact(&mut octets).await?;
+            Target::Addr(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        atyp::DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            let mut domain = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut domain).await?;
+            Target::Domain(
+                String::from_utf8(domain).map_err(|_| anyhow!("domain name is not valid UTF-8"))?,
+            )
+        }
+        other => return Err(anyhow!("unsupported SOCKS ATYP: 0x{:02x}", other)),
+    };
+
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+
+    Ok((command, target, u16::from_be_bytes(port)))
+}
+
+/// Write a reply whose `BND.ADDR`/`BND.PORT` are meaningless (there's no
+/// local socket backing a Tor stream), following the common SOCKS-over-Tor
+/// convention of reporting `0.0.0.0:0`.
+async fn write_addr_reply<S: AsyncWrite + Unpin>(stream: &mut S, code: u8) -> AnyResult<()> {
+    let reply = [SOCKS_VERSION, code, 0x00, atyp::IPV4, 0, 0, 0, 0, 0, 0];
+    stream.write_all(&reply).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Write a successful `RESOLVE` reply carrying the resolved address.
+async fn write_resolved_reply<S: AsyncWrite + Unpin>(stream: &mut S, addr: IpAddr) -> AnyResult<()> {
+    let mut reply = vec![SOCKS_VERSION, reply::SUCCEEDED, 0x00];
+    match addr {
+        IpAddr::V4(v4) => {
+            reply.push(atyp::IPV4);
+            reply.extend_from_slice(&v4.octets());
+        }
+        IpAddr::V6(v6) => {
+            reply.push(atyp::IPV6);
+            reply.extend_from_slice(&v6.octets());
+        }
+    }
+    reply.extend_from_slice(&[0, 0]); // DST.PORT is unused for RESOLVE.
+
+    stream.write_all(&reply).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Bidirectionally copy bytes between `client` and `tor_stream` until both
+/// directions have run to completion. Each direction closes its
+/// destination's write half once its source hits EOF, so a client that
+/// half-closes after sending its request (e.g. `Connection: close`) still
+/// gets the full response instead of having the still-in-flight reply
+/// abandoned by a `select` over the two copies.
+async fn pump<C, T>(client: C, tor_stream: T) -> AnyResult<()>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut client_r, mut client_w) = client.split();
+    let (mut tor_r, mut tor_w) = tor_stream.split();
+
+    let client_to_tor = async {
+        let _ = copy(&mut client_r, &mut tor_w).await;
+        let _ = tor_w.close().await;
+    };
+    let tor_to_client = async {
+        let _ = copy(&mut tor_r, &mut client_w).await;
+        let _ = client_w.close().await;
+    };
+
+    futures::future::join(client_to_tor, tor_to_client).await;
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::tor_circmgr::TorCircuitManager;
+use crate::tor_circmgr::{CongestionControl, TorCircuitManager};
 
 use log::info;
 use anyhow::{anyhow, Result as AnyResult};
@@ -12,7 +12,7 @@ struct TorClient {
 impl TorClient {
     async fn new() -> AnyResult<Self> {
         let runtime = PreferredRuntime::current()?;
-        let circ_manager = TorCircuitManager::new(runtime)?;
+        let circ_manager = TorCircuitManager::new(runtime, CongestionControl::default())?;
 
         circ_manager.init().await?;
         
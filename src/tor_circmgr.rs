@@ -1,10 +1,14 @@
+use crate::relay_diversity::{same_family, same_slash16};
+use crate::socks_proxy::SocksProxy;
 use crate::tor_chanmgr::TorChannelManager;
 
 use log::info;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::net::SocketAddr;
 use futures::task::SpawnExt;
 use anyhow::{anyhow, Result as AnyResult};
+use rand::Rng;
 
 use arti_client::{TorClient, TorClientConfig};
 
@@ -13,19 +17,88 @@ use tor_units::Percentage;
 use tor_llcrypto::pk::rsa::RsaIdentity;
 use tor_chanmgr::{ChannelUsage, ChanProvenance};
 use tor_linkspec::{ChanTarget, CircTarget, HasRelayIds, IntoOwnedChanTarget, OwnedChanTarget, OwnedCircTarget};
+use tor_netdir::{NetDir, Relay, WeightRole};
+use tor_netdoc::doc::netstatus::RelayFlags;
 use tor_proto::circuit::{ClientCirc, PendingClientCirc, CircParameters};
 use tor_proto::ccparams::{
     Algorithm, CongestionControlParamsBuilder, FixedWindowParamsBuilder,
-    RoundTripEstimatorParamsBuilder, CongestionWindowParamsBuilder
+    RoundTripEstimatorParamsBuilder, CongestionWindowParamsBuilder, VegasParamsBuilder
 };
 
+/// Which circuit-level congestion-control algorithm new circuits should use.
+///
+/// `FixedWindow` is the original fixed SENDME/cwnd window. `Vegas` (Tor
+/// proposal 324) instead sizes the window off measured RTT: it grows the
+/// window in slow start until the estimated queuing delay crosses `gamma`,
+/// then in steady state nudges the window up or down against `alpha`/`beta`
+/// thresholds, giving adaptive throughput on long paths instead of a static
+/// window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CongestionControl {
+    #[default]
+    FixedWindow,
+    Vegas,
+}
+
+/// Which position in a multi-hop path a hop occupies, used to pick the
+/// [`WeightRole`] and [`RelayFlags`] that [`TorCircuitManager::build_circuit`]
+/// applies when sampling that hop from the consensus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HopPosition {
+    Guard,
+    Middle,
+    Exit,
+}
+
+impl HopPosition {
+    fn weight_role(self) -> WeightRole {
+        match self {
+            HopPosition::Guard => WeightRole::Guard,
+            HopPosition::Middle => WeightRole::Middle,
+            HopPosition::Exit => WeightRole::Exit,
+        }
+    }
+
+    /// Flags this position requires. Guards and exits must also be `STABLE`,
+    /// per Tor's path-selection rules for hops expected to live for the
+    /// duration of a long-lived circuit.
+    fn required_flags(self) -> RelayFlags {
+        match self {
+            HopPosition::Guard => RelayFlags::GUARD | RelayFlags::STABLE,
+            HopPosition::Middle => RelayFlags::empty(),
+            HopPosition::Exit => RelayFlags::EXIT | RelayFlags::STABLE,
+        }
+    }
+}
+
+/// Token identifying an isolated slot in [`TorCircuitManager`]'s circuit
+/// pool. A fixed default token backs the legacy single-circuit API
+/// ([`TorCircuitManager::create`]/[`TorCircuitManager::extend`]/
+/// [`TorCircuitManager::get_circ`]); callers that want independent logical
+/// clients to never share a path pick their own token and go through
+/// [`TorCircuitManager::create_isolated`]/[`TorCircuitManager::get_circ_for`]
+/// instead.
 pub struct TorCircuitManager<R: Runtime> {
     tor_chan_mgr: TorChannelManager<R>,
-    circ: Option<Arc<ClientCirc>>,
+    /// Circuits pooled by isolation token. A second [`Self::create_isolated`]
+    /// under the same token replaces whatever was there; distinct tokens
+    /// never share a path.
+    circuits: Mutex<HashMap<String, Arc<ClientCirc>>>,
     runtime: R,
+    /// Congestion-control algorithm used by [`Self::create`]/[`Self::extend`]
+    /// calls that don't pass an explicit override.
+    congestion_control: CongestionControl,
+    /// When `true`, channels opened for [`ChannelUsage::Dir`] circuits still
+    /// negotiate link padding as though they carried interactive traffic,
+    /// instead of the default of suppressing padding on directory-only
+    /// channels to save bandwidth. See [`Self::set_negotiate_dir_padding`].
+    negotiate_dir_padding: bool,
 }
 
 impl<R: Runtime> TorCircuitManager<R> {
+    /// Isolation token backing the legacy single-circuit API.
+    const DEFAULT_ISOLATION: &'static str = "__default__";
+
     async fn create_common<CT: ChanTarget>(
         &self,
         rt: &R,
@@ -41,6 +114,18 @@ impl<R: Runtime> TorCircuitManager<R> {
             Ok((chan, _)) => chan,
             Err(_) => return Err(anyhow!("Failed to get or launch channel")),
         };
+
+        // Tell the channel frontend how this circuit will be used so it can
+        // negotiate (or suppress) link padding accordingly: directory-only
+        // channels skip padding by default to save bandwidth, unless the
+        // caller has opted in via `negotiate_dir_padding`.
+        let padding_usage = match usage {
+            ChannelUsage::Dir if self.negotiate_dir_padding => ChannelUsage::UserTraffic,
+            other => other,
+        };
+        chan.note_usage(padding_usage)
+            .map_err(|_| anyhow!("Failed to negotiate channel padding"))?;
+
         // Construct the (zero-hop) circuit.
         let (pending_circ, reactor) = chan.new_circ()
             .await.map_err(|_| anyhow!("Failed to create circuit"))?;
@@ -71,9 +156,14 @@ impl<R: Runtime> TorCircuitManager<R> {
         relay_port: u16,
         relay_fingerprint: &str,
     ) -> AnyResult<OwnedCircTarget> {
-        let addr = format!("{}:{}", relay_ip, relay_port)
-            .parse::<SocketAddr>()
+        // Parse the host and port separately rather than formatting
+        // `"{ip}:{port}"` and parsing that as a `SocketAddr`: an unbracketed
+        // IPv6 address (e.g. from `relay_addr`) has colons that make the
+        // combined string ambiguous and fail to parse.
+        let ip: std::net::IpAddr = relay_ip
+            .parse()
             .map_err(|e| anyhow!("Invalid address: {}", e))?;
+        let addr = SocketAddr::new(ip, relay_port);
 
         tokio::time::timeout(
             std::time::Duration::from_secs(10),
@@ -107,6 +197,58 @@ impl<R: Runtime> TorCircuitManager<R> {
         Ok(target)
     }
 
+    /// Weighted-sample a single relay for `position`, excluding relays
+    /// already in `chosen` and any that share a /16 or a declared family
+    /// with one of them.
+    ///
+    /// Uses weighted reservoir sampling (algorithm A-Res): each candidate
+    /// draws `u ~ Uniform(0,1)` keyed by `u.powf(1.0 / weight)`, where
+    /// `weight` is the relay's consensus bandwidth weight for `position`
+    /// (the `bandwidth-weights` scaling factors for that position, e.g.
+    /// Wgg/Wgd for a guard or Weg/Wed/Wee for an exit, are already applied
+    /// by [`NetDir::relay_weight`]); the candidate with the largest key
+    /// wins. Relays with zero weight are never selected.
+    fn pick_weighted_hop<'a>(
+        &self,
+        netdir: &'a NetDir,
+        chosen: &[Relay<'a>],
+        position: HopPosition,
+    ) -> AnyResult<Relay<'a>> {
+        let required_flags = position.required_flags();
+        let role = position.weight_role();
+        let mut rng = rand::thread_rng();
+        let mut best: Option<(f64, Relay<'a>)> = None;
+
+        for relay in netdir.relays() {
+            if !relay.rs().flags().contains(required_flags) {
+                continue;
+            }
+            if chosen.iter().any(|c| c.rsa_id() == relay.rsa_id()) {
+                continue;
+            }
+            if chosen.iter().any(|c| same_slash16(c, &relay)) {
+                continue;
+            }
+            if chosen.iter().any(|c| same_family(c, &relay)) {
+                continue;
+            }
+
+            let weight: u64 = netdir.relay_weight(&relay, role).into();
+            if weight == 0 {
+                continue;
+            }
+
+            let u: f64 = rng.gen_range(0.0..1.0);
+            let key = u.powf(1.0 / weight as f64);
+            if best.as_ref().map(|(best_key, _)| key > *best_key).unwrap_or(true) {
+                best = Some((key, relay));
+            }
+        }
+
+        best.map(|(_, relay)| relay)
+            .ok_or_else(|| anyhow!("No relay satisfies the {:?} position's constraints", position))
+    }
+
     #[allow(dead_code)]
     async fn inner_create_one_hop(
         &self,
@@ -135,14 +277,16 @@ impl<R: Runtime> TorCircuitManager<R> {
         handshake_res.map_err(|_| anyhow!("Failed to create first hop: {}", ct.to_logged().to_string()))
     }
 
-    pub fn new(runtime: R) -> AnyResult<Self> {
+    pub fn new(runtime: R, congestion_control: CongestionControl) -> AnyResult<Self> {
         let tor_chan_mgr = TorChannelManager::new(runtime.clone())
             .map_err(|e| anyhow!("Failed to create channel manager: {}", e))?;
 
         Ok(Self {
             tor_chan_mgr,
-            circ: None,
+            circuits: Mutex::new(HashMap::new()),
             runtime,
+            congestion_control,
+            negotiate_dir_padding: false,
         })
     }
 
@@ -154,83 +298,217 @@ impl<R: Runtime> TorCircuitManager<R> {
         self.tor_chan_mgr.init(&netdir)
     }
 
+    /// Opt directory-fetch (`ChannelUsage::Dir`) circuits in or out of
+    /// negotiated channel padding. Off by default, matching Tor's usual
+    /// preference to leave bandwidth-cheap directory-only channels unpadded.
+    pub fn set_negotiate_dir_padding(&mut self, enable: bool) {
+        self.negotiate_dir_padding = enable;
+    }
+
+    /// Serve a local SOCKS5 proxy on `listen_addr` that forwards every
+    /// accepted connection over this manager's established circuit. Runs
+    /// until the listener errors; callers that want this alongside other
+    /// work should spawn it rather than awaiting it inline.
+    pub async fn run_socks_proxy(self: &Arc<Self>, listen_addr: SocketAddr) -> AnyResult<()> {
+        SocksProxy::new(self.runtime.clone(), Arc::clone(self)).run(listen_addr).await
+    }
+
     pub fn get_circ(&self) -> AnyResult<Arc<ClientCirc>> {
-        match self.circ {
-            Some(_) => {
-                let circ = self.circ.as_ref().unwrap().clone();
-                Ok(circ)
-            },
-            None => Err(anyhow!("No circuit to extend"))
+        self.get_circ_for(Self::DEFAULT_ISOLATION)?
+            .ok_or_else(|| anyhow!("No circuit to extend"))
+    }
+
+    /// Look up the circuit pooled under `token`, reaping it first if its
+    /// reactor has already exited (e.g. the remote end closed it), so
+    /// callers are never handed a dead circuit. Returns `Ok(None)` if
+    /// nothing is pooled under `token`, or the pooled circuit was dirty and
+    /// got reaped — in which case the caller should build a fresh one with
+    /// [`Self::create_isolated`].
+    pub fn get_circ_for(&self, token: &str) -> AnyResult<Option<Arc<ClientCirc>>> {
+        let mut circuits = self.circuits.lock()
+            .map_err(|_| anyhow!("Circuit pool lock poisoned"))?;
+
+        match circuits.get(token) {
+            Some(circ) if circ.is_closing() => {
+                circuits.remove(token);
+                Ok(None)
+            }
+            Some(circ) => Ok(Some(circ.clone())),
+            None => Ok(None),
         }
     }
 
+    /// Drop every pooled circuit whose reactor has already exited, freeing
+    /// their tokens for [`Self::create_isolated`] to reuse.
+    pub fn reap_closed_circuits(&self) -> AnyResult<()> {
+        let mut circuits = self.circuits.lock()
+            .map_err(|_| anyhow!("Circuit pool lock poisoned"))?;
+        circuits.retain(|_, circ| !circ.is_closing());
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub async fn create_one_hop(
-        &self, 
+        &self,
         relay_ip: &str,
         relay_port: u16,
-        relay_fingerprint: &str
+        relay_fingerprint: &str,
+        usage: ChannelUsage,
     ) -> AnyResult<Arc<ClientCirc>> {
         let mut circ_target = self.circ_target_from_relay(relay_ip, relay_port, relay_fingerprint).await?;
         let char_target = circ_target.chan_target_mut().clone();
-        let cc_params = self.build_circuit_params()?;
+        let cc_params = self.build_circuit_params(self.congestion_control)?;
         let circ_params = CircParameters::new(true, cc_params);
 
-        let client_circ = self.inner_create_one_hop(&char_target, &circ_params, ChannelUsage::UserTraffic)
+        let client_circ = self.inner_create_one_hop(&char_target, &circ_params, usage)
             .await?;
 
         Ok(client_circ)
     }
 
     pub async fn create(
-        &mut self,
+        &self,
         relay_ip: &str,
         relay_port: u16,
         relay_fingerprint: &str
+    ) -> AnyResult<Arc<ClientCirc>> {
+        self.create_with_cc(relay_ip, relay_port, relay_fingerprint, self.congestion_control, ChannelUsage::UserTraffic).await
+    }
+
+    /// Like [`Self::create`], but overriding the congestion-control
+    /// algorithm chosen at construction time and the channel-usage hint
+    /// (which drives padding negotiation, see [`Self::set_negotiate_dir_padding`])
+    /// for just this circuit.
+    pub async fn create_with_cc(
+        &self,
+        relay_ip: &str,
+        relay_port: u16,
+        relay_fingerprint: &str,
+        cc: CongestionControl,
+        usage: ChannelUsage,
+    ) -> AnyResult<Arc<ClientCirc>> {
+        self.create_isolated_with_cc(Self::DEFAULT_ISOLATION, relay_ip, relay_port, relay_fingerprint, cc, usage)
+            .await
+    }
+
+    /// Create a new circuit dedicated to `token`, pooling it alongside (not
+    /// instead of) whatever is pooled under every other token. Distinct
+    /// tokens never share a path, giving independent logical clients (e.g.
+    /// separate SOCKS sessions) basic stream isolation from one another. A
+    /// second call under the same token replaces whatever was pooled there.
+    pub async fn create_isolated(
+        &self,
+        token: &str,
+        relay_ip: &str,
+        relay_port: u16,
+        relay_fingerprint: &str,
+    ) -> AnyResult<Arc<ClientCirc>> {
+        self.create_isolated_with_cc(token, relay_ip, relay_port, relay_fingerprint, self.congestion_control, ChannelUsage::UserTraffic)
+            .await
+    }
+
+    /// Like [`Self::create_isolated`], but overriding the congestion-control
+    /// algorithm and channel-usage hint for just this circuit.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_isolated_with_cc(
+        &self,
+        token: &str,
+        relay_ip: &str,
+        relay_port: u16,
+        relay_fingerprint: &str,
+        cc: CongestionControl,
+        usage: ChannelUsage,
     ) -> AnyResult<Arc<ClientCirc>> {
         let circ_target = self.circ_target_from_relay(relay_ip, relay_port, relay_fingerprint)
             .await?;
-        let cc_params = self.build_circuit_params()?;
+        let cc_params = self.build_circuit_params(cc)?;
         let circ_params = CircParameters::new(true, cc_params);
 
-        let client_circ = self.inner_create(&circ_target, &circ_params, ChannelUsage::UserTraffic)
+        let client_circ = self.inner_create(&circ_target, &circ_params, usage)
             .await?;
 
-        self.circ = Some(client_circ.clone());
+        self.circuits.lock()
+            .map_err(|_| anyhow!("Circuit pool lock poisoned"))?
+            .insert(token.to_string(), client_circ.clone());
 
         Ok(client_circ)
     }
 
     pub async fn extend(
-        &mut self,
+        &self,
         relay_ip: &str,
         relay_port: u16,
         relay_fingerprint: &str
     ) -> AnyResult<Arc<ClientCirc>> {
-        match self.circ {
-            Some(_) => {
-                let circ = self.circ.as_ref().unwrap().clone();
-                let circ_target = self.circ_target_from_relay(relay_ip, relay_port, relay_fingerprint)
-                .await?;
-                let cc_params = self.build_circuit_params()?;
-                let circ_params = CircParameters::new(true, cc_params);
-
-                circ.extend_ntor(&circ_target, &circ_params).await?;
-
-                Ok(circ)
-            },
-            None => Err(anyhow!("No circuit to extend"))
-        }
+        self.extend_with_cc(relay_ip, relay_port, relay_fingerprint, self.congestion_control).await
     }
 
-    fn build_circuit_params(&self) -> AnyResult<tor_proto::ccparams::CongestionControlParams> {
-        let params = FixedWindowParamsBuilder::default()
-            .circ_window_start(1000)
-            .circ_window_min(100)
-            .circ_window_max(1000)
-            .build()
-            .map_err(|e| anyhow!("Failed to build fixed window params: {}", e))?;
+    /// Like [`Self::extend`], but overriding the congestion-control
+    /// algorithm chosen at construction time for just this hop.
+    pub async fn extend_with_cc(
+        &self,
+        relay_ip: &str,
+        relay_port: u16,
+        relay_fingerprint: &str,
+        cc: CongestionControl,
+    ) -> AnyResult<Arc<ClientCirc>> {
+        let circ = self.get_circ_for(Self::DEFAULT_ISOLATION)?
+            .ok_or_else(|| anyhow!("No circuit to extend"))?;
 
+        let circ_target = self.circ_target_from_relay(relay_ip, relay_port, relay_fingerprint)
+            .await?;
+        let cc_params = self.build_circuit_params(cc)?;
+        let circ_params = CircParameters::new(true, cc_params);
+
+        circ.extend_ntor(&circ_target, &circ_params).await?;
+
+        Ok(circ)
+    }
+
+    /// Build a fresh `num_hops`-hop circuit, picking every hop automatically
+    /// from the current consensus instead of requiring the caller to supply
+    /// relay IPs/fingerprints. The first hop is sampled as a guard, the last
+    /// as an exit, and any hops in between as middles, each drawn with
+    /// [`Self::pick_weighted_hop`] and excluding relays already chosen in
+    /// this path.
+    pub async fn build_circuit(&self, num_hops: usize) -> AnyResult<Arc<ClientCirc>> {
+        if num_hops == 0 {
+            return Err(anyhow!("A circuit needs at least one hop"));
+        }
+
+        let netdir = self.tor_chan_mgr.netdir()?;
+        let mut chosen: Vec<Relay<'_>> = Vec::with_capacity(num_hops);
+
+        for hop in 0..num_hops {
+            let position = if hop == 0 {
+                HopPosition::Guard
+            } else if hop == num_hops - 1 {
+                HopPosition::Exit
+            } else {
+                HopPosition::Middle
+            };
+
+            let relay = self.pick_weighted_hop(&netdir, &chosen, position)?;
+            chosen.push(relay);
+        }
+
+        let mut hops = chosen.into_iter();
+        let first = hops.next().expect("num_hops > 0 checked above");
+        let (relay_ip, relay_port) = relay_addr(&first)?;
+        let fingerprint = hex::encode(first.rsa_id().as_bytes());
+
+        let mut client_circ = self.create(&relay_ip, relay_port, &fingerprint).await?;
+
+        for relay in hops {
+            let (relay_ip, relay_port) = relay_addr(&relay)?;
+            let fingerprint = hex::encode(relay.rsa_id().as_bytes());
+            client_circ = self.extend(&relay_ip, relay_port, &fingerprint).await?;
+        }
+
+        Ok(client_circ)
+    }
+
+    fn build_circuit_params(&self, cc: CongestionControl) -> AnyResult<tor_proto::ccparams::CongestionControlParams> {
         let rtt_params = RoundTripEstimatorParamsBuilder::default()
             .ewma_cwnd_pct(Percentage::new(50))
             .ewma_max(10)
@@ -250,11 +528,56 @@ impl<R: Runtime> TorCircuitManager<R> {
             .build()
             .map_err(|e| anyhow!("Failed to build congestion window parameters: {}", e))?;
 
+        let alg = match cc {
+            CongestionControl::FixedWindow => {
+                let params = FixedWindowParamsBuilder::default()
+                    .circ_window_start(1000)
+                    .circ_window_min(100)
+                    .circ_window_max(1000)
+                    .build()
+                    .map_err(|e| anyhow!("Failed to build fixed window params: {}", e))?;
+
+                Algorithm::FixedWindow(params)
+            }
+            CongestionControl::Vegas => {
+                // `gamma`/`alpha`/`beta` are queue-occupancy thresholds
+                // (queue = cwnd * (rtt_cur - rtt_min) / rtt_cur) in units of
+                // `sendme_inc`-sized cells: slow start doubles cwnd each
+                // SENDME until queue exceeds `gamma`; steady state then
+                // grows cwnd by `cwnd_inc` while queue < alpha, shrinks it
+                // while queue > beta, and holds it steady in between.
+                let sendme_inc: u32 = 31;
+                let params = VegasParamsBuilder::default()
+                    .cwnd_params(cwnd_params.clone())
+                    .gamma(2 * sendme_inc)
+                    .alpha(sendme_inc)
+                    .beta(2 * sendme_inc)
+                    .alpha_exit(sendme_inc)
+                    .beta_exit(2 * sendme_inc)
+                    .delta(5 * sendme_inc)
+                    .build()
+                    .map_err(|e| anyhow!("Failed to build Vegas params: {}", e))?;
+
+                Algorithm::Vegas(params)
+            }
+        };
+
         CongestionControlParamsBuilder::default()
             .rtt_params(rtt_params)
             .cwnd_params(cwnd_params)
-            .alg(Algorithm::FixedWindow(params))
+            .alg(alg)
             .build()
             .map_err(|e| anyhow!("Failed to build CC params: {}", e))
     }
+}
+
+/// A relay's first known address/port, in the `(ip_string, port)` shape
+/// [`TorCircuitManager::create`]/[`TorCircuitManager::extend`] take.
+fn relay_addr(relay: &Relay<'_>) -> AnyResult<(String, u16)> {
+    let addr = relay
+        .addrs()
+        .first()
+        .ok_or_else(|| anyhow!("Relay has no known address"))?;
+
+    Ok((addr.ip().to_string(), addr.port()))
 }
\ No newline at end of file
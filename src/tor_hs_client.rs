@@ -1,17 +1,40 @@
+use crate::error::{HsError, HsResult};
+use crate::http::{self, HttpRequest, HttpResponse};
+use crate::hs_pool::{HsConnectionPool, PooledConn};
+use crate::hs_path::HsHop;
+use crate::keystore::KeyStore;
 use crate::tor_hs_connector::{TorHSConnector, OnionCertificateVerifier};
 
 use log::info;
 use std::sync::Arc;
-use std::time::Duration;
 use std::convert::TryFrom;
 use std::collections::HashMap;
+use std::time::Duration;
 use anyhow::{anyhow, Result as AnyResult};
 use rustls::{ClientConfig, ServerName};
 use tokio_rustls::TlsConnector;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::AsyncWriteExt;
+use tor_netdir::WeightRole;
+
+/// How long an idle pooled connection is kept before it's evicted.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many idle connections are kept per `(onion_addr, port)`.
+const DEFAULT_MAX_PER_HOST: usize = 4;
+/// How long to wait for a full response before giving up. Without this, a
+/// keep-alive response with neither `Content-Length` nor
+/// `Transfer-Encoding: chunked` would make `http::read_response` block on
+/// the socket until the server closes it, which a keep-alive server never
+/// does on its own.
+const RESPONSE_READ_TIMEOUT: Duration = Duration::from_secs(20);
 
 pub struct TorHSClient {
     hs_client: TorHSConnector,
+    /// Whether to transparently decode a compressed response body. Callers
+    /// that want the raw bytes (e.g. to re-serve them as-is) can disable this.
+    decompress: bool,
+    /// Idle keep-alive connections, reused across calls to avoid rebuilding
+    /// the rendezvous circuit and stream for every request.
+    pool: Arc<HsConnectionPool>,
 }
 
 impl TorHSClient {
@@ -19,14 +42,48 @@ impl TorHSClient {
         let hs_client = TorHSConnector::new()?;
 
         Ok(Self {
-            hs_client
+            hs_client,
+            decompress: true,
+            pool: HsConnectionPool::new(DEFAULT_IDLE_TIMEOUT, DEFAULT_MAX_PER_HOST),
         })
     }
 
-    pub async fn init(&mut self, storage: Option<&HashMap<String, String>>) -> AnyResult<()> {
+    /// Enable or disable transparent `Content-Encoding` decompression of
+    /// fetched bodies. Enabled by default.
+    #[allow(dead_code)]
+    pub fn set_decompress(&mut self, decompress: bool) {
+        self.decompress = decompress;
+    }
+
+    pub async fn init(&mut self, storage: Option<&HashMap<String, String>>) -> HsResult<()> {
         self.hs_client.init(storage).await
     }
 
+    /// Like [`init`](Self::init), but hands `keystore` to the connector for
+    /// client-authorization x25519 key lookups. A key found there is only
+    /// logged; it is not applied to the connection.
+    pub async fn init_with_keystore(
+        &mut self,
+        storage: Option<&HashMap<String, String>>,
+        keystore: Arc<dyn KeyStore>,
+    ) -> HsResult<()> {
+        self.hs_client.init_with_keystore(storage, keystore).await
+    }
+
+    /// Draw `k` relays matching `relay_flags`/`ipv6_required`, weighted by
+    /// consensus bandwidth for the given `role` (Guard/Middle/Exit/HsDir).
+    pub async fn select_relays_weighted(
+        &self,
+        relay_flags: u32,
+        ipv6_required: bool,
+        role: WeightRole,
+        k: usize,
+    ) -> HsResult<Vec<String>> {
+        self.hs_client
+            .select_relays_weighted(relay_flags, ipv6_required, role, k)
+            .await
+    }
+
     #[allow(dead_code)]
     pub fn set_custom_hs_relay_ids(
         &self,
@@ -43,144 +100,113 @@ impl TorHSClient {
         Ok(())
     }
 
-    pub async fn connect_to_hs(&self, hs_addr: &str, hs_port: u16) -> AnyResult<String> {
-        // Create a new stream to the hidden service
-        let tcp_stream = match self.hs_client.connect_to_hs(hs_addr, hs_port).await {
-            Ok(stream) => stream,
-            Err(e) => return Err(anyhow!("Failed to begin stream: {}", e)),
+    /// Set an arbitrary-length custom circuit, where each hop is either a
+    /// specific relay or a set of constraints to fill in via weighted
+    /// selection. Supersedes [`Self::set_custom_hs_relay_ids`]'s fixed
+    /// guard/middle/exit triple.
+    #[allow(dead_code)]
+    pub async fn set_custom_hs_path(&self, hops: &[HsHop]) -> HsResult<()> {
+        self.hs_client.set_custom_hs_path(hops).await
+    }
+
+    /// Fetch `/` from the hidden service and return the body as a lossy
+    /// string, for callers that just want the old one-shot behavior.
+    pub async fn connect_to_hs(&self, hs_addr: &str, hs_port: u16) -> HsResult<String> {
+        let response = self.request_to_hs(hs_addr, hs_port, HttpRequest::get("/")).await?;
+
+        Ok(String::from_utf8_lossy(&response.body).to_string())
+    }
+
+    /// Send a fully custom request (method, path, headers, body) to the
+    /// hidden service and return the structured response: status code,
+    /// headers, and decoded body.
+    ///
+    /// Reuses a pooled keep-alive connection to `(hs_addr, hs_port)` when one
+    /// is available, and returns the connection to the pool afterwards
+    /// unless the response says `Connection: close`.
+    pub async fn request_to_hs(
+        &self,
+        hs_addr: &str,
+        hs_port: u16,
+        request: HttpRequest,
+    ) -> HsResult<HttpResponse> {
+        let mut conn = match self.pool.take(hs_addr, hs_port).await {
+            Some(conn) => conn,
+            None => self.open_conn(hs_addr, hs_port).await?,
         };
 
-        if hs_port == 443 {
-            // For HTTPS, we need a TLS connection
-            return self.handle_https_connection(tcp_stream, hs_addr).await;
-        } else {
-            return self.handle_http_connection(tcp_stream, hs_addr).await;
+        let response = self.send_request(&mut conn, hs_addr, request).await?;
+
+        if !wants_close(&response) {
+            self.pool.put(hs_addr, hs_port, conn).await;
         }
+
+        Ok(response)
     }
 
-    async fn handle_https_connection<S>(&self, tcp_stream: S, hs_addr: &str) -> AnyResult<String> 
-    where 
-        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static
-    {
+    /// Open a fresh connection to the hidden service, performing the TLS
+    /// handshake up front for port 443.
+    async fn open_conn(&self, hs_addr: &str, hs_port: u16) -> HsResult<PooledConn> {
+        let tcp_stream = self.hs_client.connect_to_hs(hs_addr, hs_port).await?;
+
+        if hs_port != 443 {
+            return Ok(PooledConn::Plain(tcp_stream));
+        }
+
         // Create a client config that will accept any certificate for .onion domains
         let client_config = ClientConfig::builder()
             .with_safe_defaults()
             .with_custom_certificate_verifier(Arc::new(OnionCertificateVerifier {}))
             .with_no_client_auth();
-            
+
         let tls_connector = TlsConnector::from(Arc::new(client_config));
-        
+
         // Convert hostname to DNS name for TLS
         let dns_name = ServerName::try_from(hs_addr)
-            .map_err(|_| anyhow!("Invalid DNS name: {}", hs_addr))?;
-            
-        // Establish TLS connection
-        let mut tls_stream = tls_connector.connect(dns_name, tcp_stream).await
-            .map_err(|e| anyhow!("TLS connection failed: {}", e))?;
-            
-        // Send HTTPS request
-        let request = format!(
-            "GET / HTTP/1.1\r\n\
-             Host: {}\r\n\
-             Connection: close\r\n\r\n",
-            hs_addr
-        );
-
-        tls_stream.write_all(request.as_bytes()).await?;
-        tls_stream.flush().await?;
-
-        // Read response
-        let mut response = Vec::new();
-        let mut buffer = [0u8; 1024];
-        
-        // Read with timeout to avoid hanging indefinitely
-        let timeout = Duration::from_secs(20);
-        let mut total_bytes = 0;
-        
-        loop {
-            let read_future = tls_stream.read(&mut buffer);
-            let read_result = tokio::time::timeout(timeout, read_future).await;
-            
-            match read_result {
-                Ok(Ok(0)) => break, // End of stream
-                Ok(Ok(n)) => {
-                    response.extend_from_slice(&buffer[..n]);
-                    total_bytes += n;
-                    info!("Received {} bytes (total: {})", n, total_bytes);
-                    
-                    // Limit the response size to avoid excessive memory usage
-                    if total_bytes > 10 * 1024 * 1024 {
-                        info!("Response exceeds 10MB, truncating");
-                        break;
-                    }
-                }
-                Ok(Err(e)) => {
-                    return Err(anyhow!("Error reading from stream: {}", e));
-                }
-                Err(_) => {
-                    return Err(anyhow!("Read operation timed out"));
-                }
-            }
-        }
-        
-        // Display the response (first 1000 bytes)
-        let response_str = String::from_utf8_lossy(&response[..std::cmp::min(1000, response.len())]);
-        info!("Total response size: {} bytes", response.len());
-        
-        Ok(response_str.to_string())
+            .map_err(|_| HsError::invalid_onion_address(anyhow!("Invalid DNS name: {}", hs_addr)))?;
+
+        let tls_stream = tls_connector.connect(dns_name, tcp_stream).await
+            .map_err(|e| HsError::tls_rejected(anyhow!("TLS connection failed: {}", e)))?;
+
+        Ok(PooledConn::Tls(Box::new(tls_stream)))
     }
-    
-    async fn handle_http_connection<S>(&self, mut stream: S, hs_addr: &str) -> AnyResult<String> 
-    where 
-        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin
-    {
-        let request = format!(
-            "GET / HTTP/1.1\r\n\
-             Host: {}\r\n\
-             Connection: close\r\n\r\n",
-            hs_addr
-        );
-
-        stream.write_all(request.as_bytes()).await?;
-        stream.flush().await?;
-
-        let mut response = Vec::new();
-        let mut buffer = [0u8; 1024];
-        
-        // Read with timeout to avoid hanging indefinitely
-        let timeout = Duration::from_secs(20);
-        let mut total_bytes = 0;
-        
-        loop {
-            let read_future = stream.read(&mut buffer);
-            let read_result = tokio::time::timeout(timeout, read_future).await;
-            
-            match read_result {
-                Ok(Ok(0)) => break, // End of stream
-                Ok(Ok(n)) => {
-                    response.extend_from_slice(&buffer[..n]);
-                    total_bytes += n;
-
-                    info!("Received {} bytes (total: {})", n, total_bytes);
-                    
-                    // Limit the response size to avoid excessive memory usage
-                    if total_bytes > 10 * 1024 * 1024 {
-                        info!("Response exceeds 10MB, truncating");
-                        break;
-                    }
-                }
-                Ok(Err(e)) => {
-                    return Err(anyhow!("Error reading from stream: {}", e));
-                }
-                Err(_) => {
-                    return Err(anyhow!("Read operation timed out"));
-                }
+
+    /// Write `request` to `conn` and read back exactly one response.
+    async fn send_request(
+        &self,
+        conn: &mut PooledConn,
+        hs_addr: &str,
+        request: HttpRequest,
+    ) -> HsResult<HttpResponse> {
+        let bytes = request.into_bytes(hs_addr);
+
+        match conn {
+            PooledConn::Plain(stream) => {
+                stream.write_all(&bytes).await.map_err(HsError::stream_timeout)?;
+                stream.flush().await.map_err(HsError::stream_timeout)?;
+                tokio::time::timeout(RESPONSE_READ_TIMEOUT, http::read_response(stream, self.decompress))
+                    .await
+                    .map_err(|e| HsError::stream_timeout(anyhow!("Timed out reading HTTP response: {}", e)))?
+                    .map_err(|e| HsError::http(anyhow!("Error reading HTTP response: {}", e)))
+            }
+            PooledConn::Tls(stream) => {
+                stream.write_all(&bytes).await.map_err(HsError::stream_timeout)?;
+                stream.flush().await.map_err(HsError::stream_timeout)?;
+                tokio::time::timeout(RESPONSE_READ_TIMEOUT, http::read_response(stream.as_mut(), self.decompress))
+                    .await
+                    .map_err(|e| HsError::stream_timeout(anyhow!("Timed out reading HTTPS response: {}", e)))?
+                    .map_err(|e| HsError::http(anyhow!("Error reading HTTPS response: {}", e)))
             }
         }
-        
-        let response_str = String::from_utf8_lossy(&response[..std::cmp::min(1000, response.len())]);
-        info!("Total response size: {} bytes", response.len());
-        
-        Ok(response_str.to_string())
     }
+}
+
+/// Whether the response tells us it closed (or will close) the connection,
+/// so the connection shouldn't be returned to the pool.
+fn wants_close(response: &HttpResponse) -> bool {
+    response
+        .headers
+        .get("connection")
+        .map(|v| v.eq_ignore_ascii_case("close"))
+        .unwrap_or(false)
 }
\ No newline at end of file
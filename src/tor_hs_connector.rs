@@ -1,27 +1,95 @@
 use anyhow::Result as AnyResult;
+use async_trait::async_trait;
 use log::info;
 use rustls::ServerName;
 use std::{collections::HashMap, sync::Arc};
 
 use arti_client::config::TorClientConfigBuilder;
 use arti_client::{DataStream, StreamPrefs, TorClient, TorClientConfig};
+use rand::Rng;
 use tor_circmgr::path::CustomHSRelaySetting;
 use tor_linkspec::{HasAddrs, HasRelayIds};
 use tor_llcrypto::pk::rsa::RsaIdentity;
-use tor_netdir::Relay;
+use tor_netdir::{Relay, WeightRole};
 use tor_netdoc::doc::netstatus::RelayFlags;
-use tor_rtcompat::PreferredRuntime;
+use tor_rtcompat::{CompoundRuntime, PreferredRuntime, Runtime};
+
+use crate::error::{HsError, HsResult};
+use crate::hs_path::HsHop;
+use crate::keystore::KeyStore;
+use crate::relay_diversity::{same_family, same_slash16};
+use crate::transport::{InterceptingTcpProvider, TcpConnectHook};
+
+/// The `item_type` client-authorization x25519 private keys are stored
+/// under, whatever their `arti_path`.
+const CLIENT_AUTH_X25519_ITEM_TYPE: &str = "client_auth_x25519";
+
+/// Runtime type the connector actually builds its `TorClient` on: the
+/// preferred runtime for everything except TCP connects, which are routed
+/// through an interceptable shim so `init_with_tcp_hook` can observe or
+/// redirect them. When no hook is supplied, a no-op hook keeps the
+/// connector on this same concrete type either way.
+type ConnectorRuntime = CompoundRuntime<
+    PreferredRuntime,
+    PreferredRuntime,
+    InterceptingTcpProvider<PreferredRuntime>,
+    PreferredRuntime,
+>;
+
+/// A [`TcpConnectHook`] that does nothing, used when the caller doesn't
+/// supply one of their own.
+struct NoopTcpHook;
+
+#[async_trait]
+impl TcpConnectHook for NoopTcpHook {
+    async fn before_connect(&self, _addr: &std::net::SocketAddr) -> std::io::Result<Option<std::net::SocketAddr>> {
+        Ok(None)
+    }
+}
 
 pub struct TorHSConnector {
-    arti_client: Option<Arc<TorClient<PreferredRuntime>>>,
+    arti_client: Option<Arc<TorClient<ConnectorRuntime>>>,
+    /// Where client-authorization x25519 keys and onion-service credentials
+    /// are looked up from, set via [`init_with_keystore`](Self::init_with_keystore).
+    /// `None` until a keystore has been supplied.
+    keystore: Option<Arc<dyn KeyStore>>,
 }
 
 impl TorHSConnector {
     pub fn new() -> AnyResult<Self> {
-        Ok(Self { arti_client: None })
+        Ok(Self {
+            arti_client: None,
+            keystore: None,
+        })
+    }
+
+    pub async fn init(&mut self, storage: Option<&HashMap<String, String>>) -> HsResult<()> {
+        self.init_with_tcp_hook(storage, Arc::new(NoopTcpHook), None).await
+    }
+
+    /// Like [`init`](Self::init), but keeps `keystore` for looking up a
+    /// client-authorization x25519 key per onion address. A key found there
+    /// is only logged by [`connect_to_hs`](Self::connect_to_hs); it is not
+    /// applied to the connection, and no other credentials are read.
+    pub async fn init_with_keystore(
+        &mut self,
+        storage: Option<&HashMap<String, String>>,
+        keystore: Arc<dyn KeyStore>,
+    ) -> HsResult<()> {
+        self.init_with_tcp_hook(storage, Arc::new(NoopTcpHook), Some(keystore)).await
     }
 
-    pub async fn init(&mut self, storage: Option<&HashMap<String, String>>) -> AnyResult<()> {
+    /// Like [`init`](Self::init), but routes every outbound TCP connect Arti
+    /// makes (to relays, directory caches, etc.) through `tcp_hook` before
+    /// handing it off to the default provider. Useful for tunneling Arti
+    /// over an existing socket, injecting latency/failures for testing, or
+    /// logging every connect the client makes.
+    pub async fn init_with_tcp_hook(
+        &mut self,
+        storage: Option<&HashMap<String, String>>,
+        tcp_hook: Arc<dyn TcpConnectHook>,
+        keystore: Option<Arc<dyn KeyStore>>,
+    ) -> HsResult<()> {
         let config = if let Some(storage_map) = storage {
             let state_dir = storage_map.get("state_dir").unwrap();
             let cache_dir = storage_map.get("cache_dir").unwrap();
@@ -34,29 +102,216 @@ impl TorHSConnector {
             TorClientConfig::default()
         };
 
+        let base_runtime = PreferredRuntime::current()
+            .or_else(|_| PreferredRuntime::create())
+            .map_err(HsError::not_bootstrapped)?;
+        let tcp_provider = InterceptingTcpProvider::new(base_runtime.clone(), tcp_hook);
+        // Reuse the preferred runtime's spawner, sleep provider, and TLS
+        // provider; only the TCP leg is swapped out.
+        let runtime = CompoundRuntime::new(
+            base_runtime.clone(),
+            base_runtime.clone(),
+            tcp_provider,
+            base_runtime,
+        );
+
         let arti_client = Arc::new(
             TorClient::builder()
                 .config(config)
-                .create_unbootstrapped()?,
+                .runtime(runtime)
+                .create_unbootstrapped()
+                .map_err(HsError::not_bootstrapped)?,
         );
 
         info!("load directory from cache");
-        arti_client.load_cache().await?;
+        arti_client.load_cache().await.map_err(HsError::not_bootstrapped)?;
         if !arti_client.dirmgr().timely_netdir().is_ok() {
             info!("bootstrap manually");
-            arti_client.bootstrap().await?;
+            arti_client.bootstrap().await.map_err(HsError::not_bootstrapped)?;
         }
 
         self.arti_client = Some(arti_client);
+        self.keystore = keystore;
 
         Ok(())
     }
 
+    /// Look up the client-authorization x25519 private key for `hs_addr` in
+    /// the keystore supplied to [`init_with_keystore`](Self::init_with_keystore),
+    /// if any. Returns `Ok(None)` both when no keystore was supplied and
+    /// when one was but has no key for this address.
+    fn client_auth_key(&self, hs_addr: &str) -> HsResult<Option<Vec<u8>>> {
+        let Some(keystore) = self.keystore.as_ref() else {
+            return Ok(None);
+        };
+
+        keystore
+            .get(&format!("onion/{}", hs_addr), CLIENT_AUTH_X25519_ITEM_TYPE)
+            .map_err(HsError::not_bootstrapped)
+    }
+
     pub fn set_custom_hs_relay_ids(&self, rsa_ids: Vec<String>) {
         CustomHSRelaySetting::set(rsa_ids);
     }
 
-    pub async fn connect_to_hs(&self, hs_addr: &str, hs_port: u16) -> AnyResult<DataStream> {
+    /// Set an arbitrary-length custom circuit, resolving any [`HsHop::Constrained`]
+    /// hops against the current consensus before handing the final ordered
+    /// list of RSA fingerprints to [`CustomHSRelaySetting`].
+    pub async fn set_custom_hs_path(&self, hops: &[HsHop]) -> HsResult<()> {
+        let ids = self.resolve_custom_path(hops).await?;
+        CustomHSRelaySetting::set(ids);
+        Ok(())
+    }
+
+    /// Resolve an ordered list of hop specs into concrete RSA fingerprints.
+    ///
+    /// Concrete hops are looked up by id; constrained hops are filled via
+    /// the same weighted sampling as [`Self::select_relays_weighted`]. Paths
+    /// that reuse a relay are rejected, as is a concrete hop sharing a /16
+    /// or a declared family with any earlier hop; constrained hops skip
+    /// such candidates only where their `exclude_same_*` settings ask.
+    pub async fn resolve_custom_path(&self, hops: &[HsHop]) -> HsResult<Vec<String>> {
+        let arti_client = self
+            .arti_client
+            .as_ref()
+            .ok_or_else(|| HsError::not_bootstrapped(anyhow::anyhow!("Arti client not initialized")))?;
+        let netdir = arti_client.dirmgr().timely_netdir().unwrap();
+
+        let mut chosen: Vec<Relay<'_>> = Vec::with_capacity(hops.len());
+
+        for (position, hop) in hops.iter().enumerate() {
+            let relay = match hop {
+                HsHop::Relay(fingerprint) => {
+                    let bytes = hex::decode(fingerprint).map_err(|_| {
+                        HsError::invalid_onion_address(anyhow::anyhow!(
+                            "Invalid RSA fingerprint at hop {}",
+                            position
+                        ))
+                    })?;
+                    let rsa_identity = RsaIdentity::from_bytes(&bytes).ok_or_else(|| {
+                        HsError::invalid_onion_address(anyhow::anyhow!(
+                            "Invalid RSA fingerprint at hop {}",
+                            position
+                        ))
+                    })?;
+                    let relay = netdir.by_id(&rsa_identity).ok_or_else(|| {
+                        HsError::circuit_failed(anyhow::anyhow!(
+                            "Relay {} not found in the current consensus",
+                            fingerprint
+                        ))
+                    })?;
+
+                    // Concrete hops aren't run through `pick_constrained_hop`,
+                    // so they don't get its diversity filtering for free —
+                    // enforce the same /16 and family checks here.
+                    if chosen.iter().any(|c| same_slash16(c, &relay)) {
+                        return Err(HsError::circuit_failed(anyhow::anyhow!(
+                            "Hop {} shares a /16 with an earlier hop in the path",
+                            position
+                        )));
+                    }
+                    if chosen.iter().any(|c| same_family(c, &relay)) {
+                        return Err(HsError::circuit_failed(anyhow::anyhow!(
+                            "Hop {} is in the same family as an earlier hop in the path",
+                            position
+                        )));
+                    }
+
+                    relay
+                }
+                HsHop::Constrained {
+                    required_flags,
+                    role,
+                    ipv6_required,
+                    exclude_same_family,
+                    exclude_same_subnet,
+                } => self.pick_constrained_hop(
+                    &netdir,
+                    &chosen,
+                    *required_flags,
+                    *role,
+                    *ipv6_required,
+                    *exclude_same_family,
+                    *exclude_same_subnet,
+                )?,
+            };
+
+            if chosen.iter().any(|c| c.rsa_id() == relay.rsa_id()) {
+                return Err(HsError::circuit_failed(anyhow::anyhow!(
+                    "Hop {} reuses a relay already in the path",
+                    position
+                )));
+            }
+
+            chosen.push(relay);
+        }
+
+        Ok(chosen
+            .iter()
+            .map(|relay| hex::encode(relay.rsa_id().as_bytes()))
+            .collect())
+    }
+
+    /// Weighted-sample a single relay for a constrained hop, excluding
+    /// relays already in `chosen` and any that violate the requested
+    /// diversity rules against them.
+    #[allow(clippy::too_many_arguments)]
+    fn pick_constrained_hop<'a>(
+        &self,
+        netdir: &'a tor_netdir::NetDir,
+        chosen: &[Relay<'a>],
+        required_flags: RelayFlags,
+        role: WeightRole,
+        ipv6_required: bool,
+        exclude_same_family: bool,
+        exclude_same_subnet: bool,
+    ) -> HsResult<Relay<'a>> {
+        let mut rng = rand::thread_rng();
+        let mut best: Option<(f64, Relay<'a>)> = None;
+
+        for relay in netdir.relays() {
+            if !relay.rs().flags().contains(required_flags) {
+                continue;
+            }
+            if ipv6_required && relay.addrs().len() <= 1 {
+                continue;
+            }
+            if chosen.iter().any(|c| c.rsa_id() == relay.rsa_id()) {
+                continue;
+            }
+            if exclude_same_family && chosen.iter().any(|c| same_family(c, &relay)) {
+                continue;
+            }
+            if exclude_same_subnet && chosen.iter().any(|c| same_slash16(c, &relay)) {
+                continue;
+            }
+
+            let weight: u64 = netdir.relay_weight(&relay, role).into();
+            if weight == 0 {
+                continue;
+            }
+
+            let u: f64 = rng.gen_range(0.0..1.0);
+            let key = u.powf(1.0 / weight as f64);
+            if best.as_ref().map(|(best_key, _)| key > *best_key).unwrap_or(true) {
+                best = Some((key, relay));
+            }
+        }
+
+        best.map(|(_, relay)| relay)
+            .ok_or_else(|| HsError::circuit_failed(anyhow::anyhow!("No relay satisfies the hop constraints")))
+    }
+
+    pub async fn connect_to_hs(&self, hs_addr: &str, hs_port: u16) -> HsResult<DataStream> {
+        validate_onion_address(hs_addr)?;
+
+        // Not yet threaded into `StreamPrefs`: `arti_client` doesn't expose a
+        // per-connect client-authorization key setter to plumb it into, so
+        // this only proves the keystore lookup path is live end to end.
+        if self.client_auth_key(hs_addr)?.is_some() {
+            info!("Found a client-authorization key for {} in the keystore", hs_addr);
+        }
+
         let mut s_prefs = StreamPrefs::new();
         s_prefs.connect_to_onion_services(arti_client::config::BoolOrAuto::Explicit(true));
         // Set IPv6 as preferred to prioritize IPv6 connections when available
@@ -66,11 +321,11 @@ impl TorHSConnector {
         let arti_client = self
             .arti_client
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Arti client not initialized"))?;
+            .ok_or_else(|| HsError::not_bootstrapped(anyhow::anyhow!("Arti client not initialized")))?;
 
         let relay_ids_str = CustomHSRelaySetting::get();
-        if relay_ids_str.len() == 3 {
-            info!("Connecting through the custom circuit:");
+        if !relay_ids_str.is_empty() {
+            info!("Connecting through the custom {}-hop circuit:", relay_ids_str.len());
 
             for (id, rsa_id) in relay_ids_str.iter().enumerate() {
                 let netdir = arti_client.dirmgr().timely_netdir().unwrap();
@@ -100,9 +355,6 @@ impl TorHSConnector {
                             c_relay.addrs()[0].port()
                         }
                     );
-                    if id == 2 {
-                        info!("");
-                    }
                 }
             }
         } else {
@@ -111,7 +363,8 @@ impl TorHSConnector {
 
         let stream = arti_client
             .connect_with_prefs((hs_addr, hs_port), &s_prefs)
-            .await?;
+            .await
+            .map_err(HsError::circuit_failed)?;
 
         Ok(stream)
     }
@@ -122,11 +375,11 @@ impl TorHSConnector {
         ipv6_required: bool,
         offset: usize,
         limit: i32,
-    ) -> AnyResult<Vec<String>> {
+    ) -> HsResult<Vec<String>> {
         let arti_client = self
             .arti_client
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Arti client not initialized"))?;
+            .ok_or_else(|| HsError::not_bootstrapped(anyhow::anyhow!("Arti client not initialized")))?;
 
         let netdir = arti_client.dirmgr().timely_netdir().unwrap();
 
@@ -173,6 +426,75 @@ impl TorHSConnector {
         Ok(result)
     }
 
+    /// Draw `k` distinct relays matching `relay_flags`/`ipv6_required`, with
+    /// probability proportional to their consensus bandwidth weight for
+    /// `role` (i.e. with the consensus `bandwidth-weights` scaling factors
+    /// for that position already applied by [`tor_netdir::NetDir::relay_weight`]).
+    ///
+    /// Uses weighted reservoir sampling without replacement (algorithm
+    /// A-Res): each candidate draws `u ~ Uniform(0,1)` and is keyed by
+    /// `u.powf(1.0 / weight)`; the `k` relays with the largest keys are kept.
+    /// Relays with zero weight are never selected.
+    pub async fn select_relays_weighted(
+        &self,
+        relay_flags: u32,
+        ipv6_required: bool,
+        role: WeightRole,
+        k: usize,
+    ) -> HsResult<Vec<String>> {
+        let arti_client = self
+            .arti_client
+            .as_ref()
+            .ok_or_else(|| HsError::not_bootstrapped(anyhow::anyhow!("Arti client not initialized")))?;
+
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let netdir = arti_client.dirmgr().timely_netdir().unwrap();
+
+        let candidates: Vec<Relay<'_>> = netdir
+            .relays()
+            .filter(|relay| {
+                let has_required_flags = relay
+                    .rs()
+                    .flags()
+                    .contains(self.u32_to_relay_flags(relay_flags));
+                let has_ipv6 = !ipv6_required || relay.addrs().len() > 1;
+
+                has_required_flags && has_ipv6
+            })
+            .collect();
+
+        let mut rng = rand::thread_rng();
+        // Reservoir kept sorted ascending by key, so `reservoir[0]` is always
+        // the smallest key currently held and the first candidate to evict.
+        let mut reservoir: Vec<(f64, &Relay<'_>)> = Vec::with_capacity(k);
+
+        for relay in &candidates {
+            let weight: u64 = netdir.relay_weight(relay, role).into();
+            if weight == 0 {
+                continue;
+            }
+
+            let u: f64 = rng.gen_range(0.0..1.0);
+            let key = u.powf(1.0 / weight as f64);
+
+            if reservoir.len() < k {
+                reservoir.push((key, relay));
+                reservoir.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            } else if key > reservoir[0].0 {
+                reservoir[0] = (key, relay);
+                reservoir.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            }
+        }
+
+        Ok(reservoir
+            .iter()
+            .map(|(_, relay)| hex::encode(relay.rsa_id().as_bytes()))
+            .collect())
+    }
+
     fn u32_to_relay_flags(&self, flags_u32: u32) -> RelayFlags {
         let mut relay_flags = RelayFlags::empty();
 
@@ -223,6 +545,36 @@ impl TorHSConnector {
     }
 }
 
+/// Reject a malformed `hs_addr` before it ever reaches `connect_with_prefs`.
+///
+/// A v3 onion address is a 56-character base32 label followed by `.onion`;
+/// anything else can never resolve, so it should fail as
+/// [`HsError::invalid_onion_address`] (permanent) rather than being handed
+/// to `connect_with_prefs` and flattened into a retriable
+/// [`HsError::circuit_failed`].
+fn validate_onion_address(hs_addr: &str) -> HsResult<()> {
+    let label = hs_addr.strip_suffix(".onion").ok_or_else(|| {
+        HsError::invalid_onion_address(anyhow::anyhow!(
+            "Address {} is not a .onion address",
+            hs_addr
+        ))
+    })?;
+
+    let valid = label.len() == 56
+        && label
+            .bytes()
+            .all(|b| matches!(b, b'a'..=b'z' | b'2'..=b'7'));
+
+    if !valid {
+        return Err(HsError::invalid_onion_address(anyhow::anyhow!(
+            "Address {} is not a valid v3 onion address",
+            hs_addr
+        )));
+    }
+
+    Ok(())
+}
+
 pub struct OnionCertificateVerifier {}
 
 impl rustls::client::ServerCertVerifier for OnionCertificateVerifier {
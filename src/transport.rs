@@ -0,0 +1,60 @@
+//! A pluggable TCP transport hook for `TorHSConnector::init`.
+//!
+//! By default Arti's own connections (to directory caches, relays, etc.)
+//! go straight out over the OS TCP stack. Wrapping the runtime's
+//! [`NetStreamProvider`] lets a caller observe or redirect those connects
+//! without touching the rest of the client: tunnel Arti over an existing
+//! socket, inject latency/failures for testing, or just log every connect.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tor_rtcompat::NetStreamProvider;
+
+/// Called before each outbound TCP connect Arti makes, with the address it
+/// is about to dial.
+#[async_trait]
+pub trait TcpConnectHook: Send + Sync {
+    /// Invoked just before delegating the connect to the default provider.
+    ///
+    /// Returning `Ok(Some(replacement))` dials `replacement` instead of
+    /// `addr` — e.g. to tunnel the connect over an already-established
+    /// socket's address, or redirect it elsewhere. Returning `Err` fails the
+    /// connect outright, without ever reaching the default provider, for
+    /// injecting connect failures in tests. `Ok(None)` dials `addr`
+    /// unchanged, for hooks that only observe (logging, latency injection).
+    async fn before_connect(&self, addr: &SocketAddr) -> std::io::Result<Option<SocketAddr>>;
+}
+
+/// Wraps an inner [`NetStreamProvider`], running a [`TcpConnectHook`] before
+/// every `connect` and otherwise delegating unchanged.
+pub struct InterceptingTcpProvider<P> {
+    inner: P,
+    hook: Arc<dyn TcpConnectHook>,
+}
+
+impl<P> InterceptingTcpProvider<P> {
+    /// Wrap `inner`, invoking `hook` before each outbound connect.
+    pub fn new(inner: P, hook: Arc<dyn TcpConnectHook>) -> Self {
+        Self { inner, hook }
+    }
+}
+
+#[async_trait]
+impl<P> NetStreamProvider<SocketAddr> for InterceptingTcpProvider<P>
+where
+    P: NetStreamProvider<SocketAddr> + Send + Sync,
+{
+    type Stream = P::Stream;
+    type Listener = P::Listener;
+
+    async fn connect(&self, addr: &SocketAddr) -> std::io::Result<Self::Stream> {
+        let target = self.hook.before_connect(addr).await?.unwrap_or(*addr);
+        self.inner.connect(&target).await
+    }
+
+    async fn listen(&self, addr: &SocketAddr) -> std::io::Result<Self::Listener> {
+        self.inner.listen(addr).await
+    }
+}